@@ -1,19 +1,25 @@
 use std::{
     cmp::{max, min},
-    io::{stdout, Write},
-    sync::mpsc::channel,
-    thread,
+    mem::swap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use crate::book::OpeningBook;
 use crate::card::*;
+use crate::rng::Rng;
+use crate::tt::{Bound, Entry, TranspositionTable};
 use crate::utility::*;
 
-// returns a vector of (card, row, column) of available moves
-fn available_moves(
+// returns a vector of (card, row, column, neighbours) of every legal move
+fn all_moves(
     board: &[[Option<Card>; 5]; 4],
-    bombs: &mut [[u8; 5]; 4],
-    deck: &Vec<Card>,
-    player: u8,
+    deck: &[Card],
 ) -> Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])> {
     let mut moves: Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])> = Default::default();
 
@@ -24,105 +30,523 @@ fn available_moves(
                 if board[i][j].is_none() {
                     // fetch neighbours
                     let neighbours = Card::get_neighbours(board, i, j, deck[d].name);
+                    moves.push((d, i, j, neighbours));
+                }
+            }
+        }
+    }
+
+    return moves;
+}
 
-                    // flag to determine whether the move has priority
-                    let mut has_priority: bool = false;
+// returns a vector of (card, row, column, neighbours) of the moves worth searching: identical
+// cards lead to the same positions so only the first one is played
+fn available_moves(
+    board: &[[Option<Card>; 5]; 4],
+    deck: &[Card],
+) -> Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])> {
+    let mut moves = all_moves(board, deck);
+    moves.retain(|&(d, _, _, _)| !deck[..d].iter().any(|c| same_card(c, &deck[d])));
+    return moves;
+}
 
-                    if bombs[i][j] > 0 {
-                        has_priority = false;
-                    }
+// cheap guess of the cards a move captures: opponent neighbours weaker on the facing side
+fn capture_estimate(
+    board: &[[Option<Card>; 5]; 4],
+    card: &Card,
+    neighbours: &[Option<Position>; 4],
+    player: u8,
+) -> u8 {
+    let values = [card.top, card.right, card.bottom, card.left];
+    let mut captures = 0;
+
+    for i in 0..4 {
+        if let Some((ny, nx)) = neighbours[i] {
+            if let Some(neighbour) = board[ny][nx].as_ref() {
+                let facing = [
+                    neighbour.top,
+                    neighbour.right,
+                    neighbour.bottom,
+                    neighbour.left,
+                ];
+                if neighbour.player != player && values[i] > facing[(i + 2) % 4] {
+                    captures += 1;
+                }
+            }
+        }
+    }
+
+    return captures;
+}
+
+// whether a move can capture anything, without playing it. bombs and bonuses only ever weaken the
+// attacker or strengthen the defender, except for the units changing values or pulling cards
+fn may_capture(
+    board: &[[Option<Card>; 5]; 4],
+    card: &Card,
+    neighbours: &[Option<Position>; 4],
+    player: u8,
+) -> bool {
+    match card.name {
+        Unit::Slayer | Unit::Swarm | Unit::Titan | Unit::Siren => return true,
+        _ => {}
+    }
+
+    let values = [card.top, card.right, card.bottom, card.left];
+    // neighbours that could at least tie, which is needed for Same
+    let mut ties = 0;
+    let mut opponent_tie = false;
 
-                    // iterate through neighbours to set priority when there are any oppenent card neighbours for that cell
-                    for i in 0..4 {
-                        // if there is a valid neighbouring cell
-                        if let Some(n) = neighbours[i] {
-                            // if there is a card in that cell
-                            if let Some(neighbour) = board[n.0][n.1].as_ref() {
-                                // if that card belongs to the opponent
-                                if neighbour.player != player {
-                                    has_priority = true;
-                                    break;
-                                }
-                            }
+    for i in 0..4 {
+        if let Some((ny, nx)) = neighbours[i] {
+            if let Some(neighbour) = board[ny][nx].as_ref() {
+                let facing = [
+                    neighbour.top,
+                    neighbour.right,
+                    neighbour.bottom,
+                    neighbour.left,
+                ][(i + 2) % 4];
+
+                if values[i] >= facing {
+                    ties += 1;
+                    if neighbour.player != player {
+                        if values[i] > facing {
+                            return true;
                         }
+                        opponent_tie = true;
                     }
+                }
+            }
+        }
+    }
 
-                    // if move has priority, add it to the beginning of the list
-                    if has_priority {
-                        moves.insert(0, (d, i, j, neighbours));
-                    }
-                    // otherwise add it to the end of the list
-                    else {
-                        moves.push((d, i, j, neighbours));
+    return ties > 1 && opponent_tie;
+}
+
+// sorts the moves so the ones most likely to be best are searched first: the best move of the
+// transposition table, then the most captures, killer moves and history, bombed cells last
+fn order_moves(
+    moves: &mut [(usize, usize, usize, [Option<(usize, usize)>; 4])],
+    board: &[[Option<Card>; 5]; 4],
+    bombs: &[[u8; 5]; 4],
+    deck: &[Card],
+    player: u8,
+    tt_move: Option<(usize, usize, usize)>,
+    context: &SearchContext,
+) {
+    let killers = context.killers[context.stats.ply as usize];
+
+    moves.sort_by_cached_key(|&(d, y, x, neighbours)| {
+        let mov = Some((d, y, x));
+        let card = &deck[d];
+
+        let mut priority: i64 = 0;
+        if mov == tt_move {
+            priority += 1 << 40;
+        }
+        priority += (capture_estimate(board, card, &neighbours, player) as i64) << 32;
+        if mov == killers[0] {
+            priority += 2 << 28;
+        } else if mov == killers[1] {
+            priority += 1 << 28;
+        }
+        priority += context.history[player as usize - 1][card.name as usize][y][x] as i64;
+        if bombs[y][x] > 0 {
+            priority -= 1 << 44;
+        }
+
+        // highest priority first
+        return -priority;
+    });
+}
+
+// whether two cards are interchangeable
+pub fn same_card(a: &Card, b: &Card) -> bool {
+    return a.name == b.name
+        && a.player == b.player
+        && (a.top, a.right, a.bottom, a.left) == (b.top, b.right, b.bottom, b.left);
+}
+
+// symmetries of the board as (mirror rows, mirror columns)
+const SYMMETRIES: [(bool, bool); 3] = [(true, false), (false, true), (true, true)];
+
+// the card as seen on the mirrored board
+fn mirror_card(card: &Card, symmetry: (bool, bool)) -> Card {
+    let mut mirrored = Card::copy(card);
+    if symmetry.0 {
+        swap(&mut mirrored.top, &mut mirrored.bottom);
+    }
+    if symmetry.1 {
+        swap(&mut mirrored.right, &mut mirrored.left);
+    }
+    return mirrored;
+}
+
+fn mirror_cell(cell: Position, symmetry: (bool, bool)) -> Position {
+    let y = if symmetry.0 { 3 - cell.0 } else { cell.0 };
+    let x = if symmetry.1 { 4 - cell.1 } else { cell.1 };
+    return (y, x);
+}
+
+// whether the position stays the same on the mirrored board, decks included
+fn is_symmetric(
+    board: &[[Option<Card>; 5]; 4],
+    bombs: &[[u8; 5]; 4],
+    deck1: &[Card],
+    deck2: &[Card],
+    symmetry: (bool, bool),
+) -> bool {
+    for i in 0..4 {
+        for j in 0..5 {
+            let (mi, mj) = mirror_cell((i, j), symmetry);
+            if bombs[i][j] != bombs[mi][mj] {
+                return false;
+            }
+            match (&board[i][j], &board[mi][mj]) {
+                (None, None) => {}
+                (Some(card), Some(other)) => {
+                    if !same_card(&mirror_card(card, symmetry), other) {
+                        return false;
                     }
                 }
+                _ => return false,
             }
         }
     }
 
-    return moves;
+    // each deck must hold every mirrored card as many times as the card itself
+    for deck in [deck1, deck2] {
+        for card in deck {
+            let mirrored = mirror_card(card, symmetry);
+            let count = deck.iter().filter(|c| same_card(c, card)).count();
+            if deck.iter().filter(|c| same_card(c, &mirrored)).count() != count {
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+// settings of a search
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    // fixed (maximum depth, pruning depth), picked by the number of cards left if None
+    pub depth: Option<(u8, u8)>,
+    // receives the progress of the search after each root move
+    pub progress: Option<Sender<Progress>>,
+    // skip root moves that mirror another move when the position is symmetric
+    pub symmetry: bool,
+    // plies of capturing moves searched past the maximum depth, 0 (the default) turns quiescence
+    // off. 2 plies make the bench suite about 4 times slower
+    pub quiescence: u8,
+    // the search gives up as soon as this is set, its result is then meaningless
+    pub stop: Option<Arc<AtomicBool>>,
+    // the search ends once this is set, keeping the root moves it completed. the first (most
+    // promising) root move is always searched to the end so there is a move to play
+    pub finish: Option<Arc<AtomicBool>>,
+    // the search finishes like with `finish` once this long has passed, it runs to the end if None
+    pub time: Option<Duration>,
+    // picks among equally good root moves with this seed, the first of them (by card, row, column) if None
+    pub seed: Option<u64>,
+    // search threads, one per core if None
+    pub threads: Option<usize>,
+    // positions found in the book are played from it without searching
+    pub book: Option<Arc<OpeningBook>>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        return SearchOptions {
+            depth: None,
+            progress: None,
+            symmetry: false,
+            quiescence: 0,
+            stop: None,
+            finish: None,
+            time: None,
+            seed: None,
+            threads: None,
+            book: None,
+        };
+    }
+}
+
+// sent by a search each time a root move is done
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Progress {
+    // root moves searched to the end so far and in total
+    pub done: usize,
+    pub total: usize,
+    // the root move just searched and its score as ((card, row, column), score)
+    pub last: ((usize, usize, usize), i8),
+    // best root move so far and its score
+    pub best: ((usize, usize, usize), i8),
+}
+
+// counters gathered while searching
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SearchStats {
+    // positions visited including the root moves
+    pub nodes: u64,
+    // moves skipped by alpha-beta pruning
+    pub cutoffs: u64,
+    // positions searched by quiescence past the maximum depth, counted in nodes as well
+    pub quiescence_nodes: u64,
+    // positions whose score was taken from the transposition table
+    pub tt_hits: u64,
+    // deepest ply reached from the root
+    pub max_depth: u8,
+    // ply of the position being searched
+    ply: u8,
+}
+
+impl SearchStats {
+    // adds up the counters of another search
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.cutoffs += other.cutoffs;
+        self.quiescence_nodes += other.quiescence_nodes;
+        self.tt_hits += other.tt_hits;
+        self.max_depth = max(self.max_depth, other.max_depth);
+    }
+}
+
+// state of a search thread carried through every node
+struct SearchContext {
+    stats: SearchStats,
+    tt: Arc<TranspositionTable>,
+    // plies of quiescence allowed past the maximum depth
+    quiescence: u8,
+    stop: Option<Arc<AtomicBool>>,
+    finish: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    // the last 2 moves that caused a cutoff on each ply
+    killers: [[Option<(usize, usize, usize)>; 2]; 17],
+    // how well each unit did on each cell for each player
+    history: [[[[u32; 5]; 4]; 9]; 2],
+}
+
+impl SearchContext {
+    fn new(tt: Arc<TranspositionTable>) -> SearchContext {
+        return SearchContext {
+            stats: SearchStats::default(),
+            tt,
+            quiescence: 0,
+            stop: None,
+            finish: None,
+            deadline: None,
+            killers: [[None; 2]; 17],
+            history: [[[[0; 5]; 4]; 9]; 2],
+        };
+    }
+
+    fn stopped(&self) -> bool {
+        return [&self.stop, &self.finish]
+            .iter()
+            .any(|flag| flag.as_ref().is_some_and(|f| f.load(Ordering::Relaxed)))
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+    }
+
+    // a move refuted the opponent's move, so it is tried early in sibling positions
+    fn remember_cutoff(&mut self, mov: (usize, usize, usize), unit: Unit, player: u8, depth: u8) {
+        let killers = &mut self.killers[self.stats.ply as usize];
+        if killers[0] != Some(mov) {
+            killers[1] = killers[0];
+            killers[0] = Some(mov);
+        }
+
+        let history = &mut self.history[player as usize - 1][unit as usize][mov.1][mov.2];
+        *history = history.saturating_add(depth as u32 * depth as u32);
+    }
 }
 
-// plays the best move on the board for current player
+// determines maximum depth of minimax algorithm & minimum depth at which the pruning can occur
+pub fn default_depth(cards_left: usize) -> (u8, u8) {
+    match cards_left {
+        1..=6 => (6, 0),
+        7 => (7, 4),
+        8 => (8, 6),
+        9..=10 => (5, 3),
+        11 => (4, 2),
+        12 => (4, 3),
+        _ => (3, 2),
+    }
+}
+
+// outcome of a search from the root
+pub struct SearchResult {
+    // best move as (card, row, column, neighbours)
+    pub best: (usize, usize, usize, [Option<Position>; 4]),
+    // minimax score of the best move
+    pub score: i8,
+    // score of each root move as ((card, row, column), score)
+    pub scores: Vec<((usize, usize, usize), i8)>,
+    // counters of the whole search
+    pub stats: SearchStats,
+    // time spent searching
+    pub elapsed: Duration,
+    // false when the search was stopped, the result then only covers the root moves searched
+    pub complete: bool,
+}
+
+impl SearchResult {
+    pub fn nodes_per_second(&self) -> f64 {
+        return self.stats.nodes as f64 / self.elapsed.as_secs_f64().max(1e-9);
+    }
+}
+
+// searches for the best move of current player
 pub fn ai(
     board: &mut [[Option<Card>; 5]; 4],
-    deck1: &mut Vec<Card>,
-    deck2: &mut Vec<Card>,
+    deck1: &[Card],
+    deck2: &[Card],
     player: u8,
     bombs: &mut [[u8; 5]; 4],
-) -> (usize, usize, usize, [Option<(usize, usize)>; 4]) {
+    options: &SearchOptions,
+    tt: &Arc<TranspositionTable>,
+) -> SearchResult {
+    let start = Instant::now();
+
     // init best move, best score and available moves
     let mut best_move: usize = 0;
     let mut best_score: i8;
-    let moves: Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])>;
+    let mut moves: Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])>;
     if player == 1 {
         best_score = -125;
-        moves = available_moves(board, bombs, deck1, 1);
+        moves = available_moves(board, deck1);
     } else {
         best_score = 125;
-        moves = available_moves(board, bombs, deck2, 2);
+        moves = available_moves(board, deck2);
     }
 
-    // determines maximum depth of minimax algorithm & minimum depth at which the pruning can occur
-    let max_depth: (u8, u8) = {
-        match deck1.len() + deck2.len() {
-            1..=6 => (6, 0),
-            7 => (7, 4),
-            8 => (8, 6),
-            9..=10 => (5, 3),
-            11 => (4, 2),
-            12 => (4, 3),
-            _ => (3, 2),
+    // a book move is played right away as long as it is legal here
+    if let Some(book) = &options.book {
+        let key = TranspositionTable::hash(board, bombs, deck1, deck2, player);
+        if let Some((mov, score)) = book.probe(key) {
+            if let Some(&best) = moves.iter().find(|m| (m.0, m.1, m.2) == mov) {
+                return SearchResult {
+                    best,
+                    score,
+                    scores: vec![(mov, score)],
+                    stats: SearchStats::default(),
+                    elapsed: start.elapsed(),
+                    complete: true,
+                };
+            }
         }
+    }
+
+    // moves left out for mirroring a searched move as (move, mirrored move)
+    let mut mirrored: Vec<((usize, usize, usize), (usize, usize, usize))> = Vec::new();
+    if options.symmetry {
+        let deck = if player == 1 { deck1 } else { deck2 };
+        let symmetries: Vec<(bool, bool)> = SYMMETRIES
+            .iter()
+            .copied()
+            .filter(|&symmetry| is_symmetric(board, bombs, deck1, deck2, symmetry))
+            .collect();
+
+        let mut kept: Vec<(usize, usize, usize, [Option<(usize, usize)>; 4])> = Vec::new();
+        for mov in moves {
+            // look for a searched move that this one mirrors
+            let image = symmetries.iter().find_map(|&symmetry| {
+                let card = mirror_card(&deck[mov.0], symmetry);
+                let d = deck.iter().position(|c| same_card(c, &card))?;
+                let (y, x) = mirror_cell((mov.1, mov.2), symmetry);
+                kept.iter()
+                    .find(|k| (k.0, k.1, k.2) == (d, y, x))
+                    .map(|k| (k.0, k.1, k.2))
+            });
+
+            match image {
+                Some(image) => mirrored.push(((mov.0, mov.1, mov.2), image)),
+                None => kept.push(mov),
+            }
+        }
+        moves = kept;
+    }
+
+    // fixed depth or the one fitting the number of cards left
+    let max_depth: (u8, u8) = options
+        .depth
+        .unwrap_or_else(|| default_depth(deck1.len() + deck2.len()));
+
+    let quiescence = options.quiescence;
+
+    // with a time limit, a search one ply deep goes first so the deadline can cut even the first
+    // root move of the full search and still leave a move to play
+    let shallow = match options.time {
+        Some(_) if max_depth.0 > 1 => Some(ai(
+            board,
+            deck1,
+            deck2,
+            player,
+            bombs,
+            &SearchOptions {
+                depth: Some((1, 0)),
+                progress: None,
+                time: None,
+                ..options.clone()
+            },
+            tt,
+        )),
+        _ => None,
     };
+    let cut_first = shallow.is_some();
+
+    // the most promising root moves are searched first so a stopped search has looked at them
+    {
+        let context = SearchContext::new(Arc::clone(tt));
+        let deck = if player == 1 { deck1 } else { deck2 };
+        order_moves(&mut moves, board, bombs, deck, player, None, &context);
+    }
 
     // init channels for communication between threads
     let (tx, rx) = channel();
 
-    // iterate through the moves
-    for m in 0..moves.len() {
-        // determine move
-        let mut mov = moves[m];
+    // each worker thread takes the next root move until there are none left
+    let next = Arc::new(AtomicUsize::new(0));
+    let root_moves = Arc::new(moves.clone());
+    let workers = options
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1);
+    for _ in 0..min(workers, moves.len()) {
+        let sender = tx.clone();
+        let next = Arc::clone(&next);
+        let moves = Arc::clone(&root_moves);
+        let mut context = SearchContext::new(Arc::clone(tt));
+        context.quiescence = quiescence;
+        context.stop = options.stop.clone();
+        let finish = options.finish.clone();
+        let deadline = options.time.map(|time| start + time);
 
-        // make a copy of the board
-        let mut t_board: [[Option<Card>; 5]; 4] = copy_board(&board);
-
-        // make a clone of the bombs
-        let mut t_bombs = bombs.clone();
-
-        // make a copy of the decks
-        let mut t_deck1: Vec<Card> = Default::default();
-        let mut t_deck2: Vec<Card> = Default::default();
-        for i in 0..deck2.len() {
-            t_deck2.push(Card::copy(&deck2[i]));
-            if i < deck1.len() {
-                t_deck1.push(Card::copy(&deck1[i]));
+        // make a copy of the position
+        let board = copy_board(board);
+        let bombs = *bombs;
+        let deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+        let deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
+
+        thread::spawn(move || loop {
+            let m = next.fetch_add(1, Ordering::Relaxed);
+            if m >= moves.len() {
+                break;
             }
-        }
+            let mut mov = moves[m];
+
+            // the first root move is always finished so a stopped search still has a move, only
+            // the deadline cuts it when the shallow search has one
+            context.finish = if m == 0 { None } else { finish.clone() };
+            context.deadline = if m == 0 && !cut_first { None } else { deadline };
+
+            let mut t_board = copy_board(&board);
+            let mut t_bombs = bombs;
+            let mut t_deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+            let mut t_deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
 
-        // spawn a thread to do the calculations
-        let sender = tx.clone();
-        thread::spawn(move || {
             // place the card down
             Card::place_card(
                 &mut t_board,
@@ -133,7 +557,16 @@ pub fn ai(
                 player,
                 &mut t_bombs,
                 &mut mov.3,
-            );
+            )
+            .expect("Generated move is illegal !");
+
+            // the root move counts as a visited position
+            context.stats = SearchStats {
+                nodes: 1,
+                max_depth: 1,
+                ply: 1,
+                ..Default::default()
+            };
 
             // calculate opponent's best score for this move
             let score = minimax(
@@ -145,11 +578,13 @@ pub fn ai(
                 -125,
                 125,
                 max_depth,
+                &mut context,
             );
 
-            // send score to main thread
+            // a stopped search has no score, send it to main thread otherwise
+            let score = if context.stopped() { None } else { Some(score) };
             sender
-                .send((m, score))
+                .send((m, score, context.stats))
                 .expect("Thread could not send info !");
         });
     }
@@ -157,13 +592,26 @@ pub fn ai(
     // close the sending channel as it is no longer needed
     drop(tx);
 
-    print!("Progress: ");
-    flush!();
+    // scores of every root move
+    let mut scores = Vec::with_capacity(moves.len());
+    let mut stats = SearchStats::default();
+    let mut complete = true;
 
     // loop through data of the recieving channel
     for data in rx {
         // break down sent data
-        let (mov, score) = data;
+        let (mov, score, move_stats) = data;
+        stats.merge(&move_stats);
+
+        // moves cut short by a stop are left out
+        let score = match score {
+            Some(score) => score,
+            None => {
+                complete = false;
+                continue;
+            }
+        };
+        scores.push(((moves[mov].0, moves[mov].1, moves[mov].2), score));
 
         // flag to see if there was a better score
         let better_score: bool = {
@@ -177,24 +625,171 @@ pub fn ai(
             }
         };
 
-        // if we have a better score, update best move
-        if better_score {
+        // if we have a better score, update best move, equal scores go to the first move
+        let key = |m: usize| (moves[m].0, moves[m].1, moves[m].2);
+        let first = scores.len() == 1;
+        if better_score || (score == best_score && (first || key(mov) < key(best_move))) {
             best_score = score;
             best_move = mov;
-            print!("({})", best_score);
-        } else {
-            print!("|");
         }
-        flush!();
+
+        if let Some(progress) = &options.progress {
+            // nobody listening anymore is fine
+            let _ = progress.send(Progress {
+                done: scores.len(),
+                total: moves.len(),
+                last: (key(mov), score),
+                best: (key(best_move), best_score),
+            });
+        }
+    }
+
+    // not even the first move was finished in time, the shallow search answers instead
+    match shallow {
+        Some(shallow) if scores.is_empty() => {
+            stats.merge(&shallow.stats);
+            return SearchResult {
+                stats,
+                elapsed: start.elapsed(),
+                complete: false,
+                ..shallow
+            };
+        }
+        _ => {}
+    }
+
+    // a seeded pick among the moves sharing the best score
+    if let Some(seed) = options.seed {
+        let mut tied: Vec<usize> = (0..moves.len())
+            .filter(|&m| {
+                let key = (moves[m].0, moves[m].1, moves[m].2);
+                scores.contains(&(key, best_score))
+            })
+            .collect();
+        tied.sort_by_key(|&m| (moves[m].0, moves[m].1, moves[m].2));
+        if !tied.is_empty() {
+            best_move = tied[Rng::new(seed).below(tied.len())];
+        }
+    }
+
+    // mirrored moves score the same as the moves they mirror
+    for (mov, image) in mirrored {
+        if let Some(&(_, score)) = scores.iter().find(|(m, _)| *m == image) {
+            scores.push((mov, score));
+        }
+    }
+    // scores come in whatever order the threads finish
+    scores.sort_by_key(|&(mov, _)| mov);
+
+    return SearchResult {
+        best: moves[best_move],
+        score: best_score,
+        scores,
+        stats,
+        elapsed: start.elapsed(),
+        complete,
+    };
+}
+
+// the thread of a running search, or the result of one that was already done
+enum Search {
+    Running(JoinHandle<SearchResult>),
+    Done(SearchResult),
+}
+
+// a search running in its own thread which can be stopped early
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    progress: Receiver<Progress>,
+    search: Search,
+}
+
+impl SearchHandle {
+    // starts searching a copy of the position, the progress and finish of the options are replaced
+    pub fn start(
+        board: &[[Option<Card>; 5]; 4],
+        deck1: &[Card],
+        deck2: &[Card],
+        player: u8,
+        bombs: &[[u8; 5]; 4],
+        options: &SearchOptions,
+        tt: &Arc<TranspositionTable>,
+    ) -> SearchHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, progress) = channel();
+        let options = SearchOptions {
+            progress: Some(sender),
+            finish: Some(Arc::clone(&stop)),
+            ..options.clone()
+        };
+
+        let mut board = copy_board(board);
+        let mut bombs = *bombs;
+        let deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+        let deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
+        let tt = Arc::clone(tt);
+        let thread = thread::spawn(move || {
+            return ai(
+                &mut board, &deck1, &deck2, player, &mut bombs, &options, &tt,
+            );
+        });
+
+        return SearchHandle {
+            stop,
+            progress,
+            search: Search::Running(thread),
+        };
+    }
+
+    // a handle over a search that is already done
+    pub fn finished(result: SearchResult) -> SearchHandle {
+        let (_, progress) = channel();
+        return SearchHandle {
+            stop: Arc::new(AtomicBool::new(false)),
+            progress,
+            search: Search::Done(result),
+        };
     }
 
-    println!();
+    // progress reports, the channel is closed once the search is over
+    pub fn progress(&self) -> &Receiver<Progress> {
+        return &self.progress;
+    }
 
-    if (player == 1 && best_score > 100) || (player == 2 && best_score < -100) {
-        println!("\n  Omae wa mou shindeiru\n");
+    // asks the search to end as soon as possible with the root moves searched so far, which
+    // always include the first one
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
     }
 
-    return moves[best_move];
+    pub fn is_finished(&self) -> bool {
+        return match &self.search {
+            Search::Done(_) => true,
+            Search::Running(thread) => thread.is_finished(),
+        };
+    }
+
+    // waits for the search to end
+    pub fn wait(self) -> SearchResult {
+        return match self.search {
+            Search::Done(result) => result,
+            Search::Running(thread) => thread.join().expect("Search thread panicked !"),
+        };
+    }
+
+    // waits for the search to end, stopping it once the time limit is reached
+    pub fn wait_for(self, limit: Duration) -> SearchResult {
+        let deadline = Instant::now() + limit;
+        while !self.is_finished() {
+            let now = Instant::now();
+            if now >= deadline {
+                self.stop();
+                break;
+            }
+            thread::sleep(min(deadline - now, Duration::from_millis(5)));
+        }
+        return self.wait();
+    }
 }
 
 fn minimax(
@@ -206,10 +801,16 @@ fn minimax(
     mut alpha: i8,
     mut beta: i8,
     depth: (u8, u8),
+    context: &mut SearchContext,
 ) -> i8 {
+    // the score no longer matters once the search is stopped
+    if context.stopped() {
+        return 0;
+    }
+
     // if player 2 is out of cards, the game is over
     if deck2.is_empty() {
-        let (p1_score, p2_score) = calc_scores(&board);
+        let (p1_score, p2_score) = calc_scores(board);
         if p1_score > p2_score {
             // maximizing player wins
             return 100 + evaluation(board);
@@ -221,19 +822,66 @@ fn minimax(
 
     // if we are out of depth, return static evaluation
     if depth.0 == 0 {
-        return evaluation(board);
+        let plies = context.quiescence;
+        return quiescence(
+            board, deck1, deck2, bombs, player, alpha, beta, plies, context,
+        );
+    }
+
+    // bounds are only reliable where every move is searched with an open alpha-beta window,
+    // which is the case once pruning is allowed
+    let bounded = depth.0 <= depth.1 && alpha < beta;
+    let window = (alpha, beta);
+
+    // look the position up in the transposition table
+    let key = TranspositionTable::with_quiescence(
+        TranspositionTable::hash(board, bombs, deck1, deck2, player),
+        context.quiescence,
+    );
+    let entry = context.tt.probe(key);
+    if let Some(entry) = entry {
+        if entry.depth >= depth.0 {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => bounded && entry.score >= beta,
+                Bound::Upper => bounded && entry.score <= alpha,
+            };
+            if usable {
+                context.stats.tt_hits += 1;
+                return entry.score;
+            }
+        }
     }
 
     // get all possible moves & init score
-    let moves: Vec<(usize, usize, usize, [Option<Position>; 4])>;
+    let mut moves: Vec<(usize, usize, usize, [Option<Position>; 4])>;
     let mut best_score: i8;
     if player == 1 {
-        moves = available_moves(board, bombs, deck1, 1);
+        moves = available_moves(board, deck1);
+        order_moves(
+            &mut moves,
+            board,
+            bombs,
+            deck1,
+            1,
+            entry.and_then(|e| e.best),
+            context,
+        );
         best_score = -125;
     } else {
-        moves = available_moves(board, bombs, deck2, 2);
+        moves = available_moves(board, deck2);
+        order_moves(
+            &mut moves,
+            board,
+            bombs,
+            deck2,
+            2,
+            entry.and_then(|e| e.best),
+            context,
+        );
         best_score = 125;
     }
+    let mut best_move = None;
 
     // iterate through moves
     for m in 0..moves.len() {
@@ -241,7 +889,7 @@ fn minimax(
         let mut mov = moves[m];
 
         // make a copy of the board
-        let temp_board: [[Option<Card>; 5]; 4] = copy_board(&board);
+        let temp_board: [[Option<Card>; 5]; 4] = copy_board(board);
 
         // save card
         let temp_card = {
@@ -251,9 +899,10 @@ fn minimax(
                 Card::copy(&deck2[mov.0])
             }
         };
+        let unit = temp_card.name;
 
         // make a copy of the bombs
-        let temp_bombs = bombs.clone();
+        let temp_bombs = *bombs;
 
         // place the card
         Card::place_card(
@@ -265,7 +914,12 @@ fn minimax(
             player,
             bombs,
             &mut mov.3,
-        );
+        )
+        .expect("Generated move is illegal !");
+
+        context.stats.nodes += 1;
+        context.stats.ply += 1;
+        context.stats.max_depth = max(context.stats.max_depth, context.stats.ply);
 
         // calculate opponent's best score for this move
         let score = minimax(
@@ -277,46 +931,980 @@ fn minimax(
             alpha,
             beta,
             (depth.0 - 1, depth.1),
+            context,
         );
 
-        if player == 1 {
-            // calculate maxmizing player's best score
-            best_score = max(best_score, score);
+        context.stats.ply -= 1;
 
-            // put the taken card back
+        // put the taken card back
+        if player == 1 {
             deck1.insert(mov.0, temp_card);
-            // revert board
-            *board = temp_board;
-            // revert bombs
-            *bombs = temp_bombs;
+        } else {
+            deck2.insert(mov.0, temp_card);
+        }
+        // revert board
+        *board = temp_board;
+        // revert bombs
+        *bombs = temp_bombs;
+
+        let cutoff = if player == 1 {
+            // calculate maxmizing player's best score
+            if score > best_score {
+                best_score = score;
+                best_move = Some((mov.0, mov.1, mov.2));
+            }
 
             // update the best score achieved by the maximizing player
             alpha = max(alpha, best_score);
 
-            // if we are deeper than the allowed pruning depth, prune if applicable
-            if depth.0 <= depth.1 && best_score >= beta {
-                break;
-            }
+            best_score >= beta
         } else {
             // calculate minimizing player's best score
-            best_score = min(best_score, score);
-
-            // put the taken card back
-            deck2.insert(mov.0, temp_card);
-            // revert board
-            *board = temp_board;
-            // revert bombs
-            *bombs = temp_bombs;
+            if score < best_score {
+                best_score = score;
+                best_move = Some((mov.0, mov.1, mov.2));
+            }
 
             // update the best score achieved by the minimizing player
             beta = min(beta, best_score);
 
-            // if we are deeper than the allowed pruning depth, prune if applicable
-            if depth.0 <= depth.1 && best_score <= alpha {
-                break;
+            best_score <= alpha
+        };
+
+        if context.stopped() {
+            return best_score;
+        }
+
+        // if we are deeper than the allowed pruning depth, prune if applicable
+        if depth.0 <= depth.1 && cutoff {
+            context.stats.cutoffs += 1;
+            context.remember_cutoff((mov.0, mov.1, mov.2), unit, player, depth.0);
+            break;
+        }
+    }
+
+    if bounded {
+        let bound = if best_score <= window.0 {
+            Bound::Upper
+        } else if best_score >= window.1 {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        context.tt.store(
+            key,
+            Entry {
+                score: best_score,
+                depth: depth.0,
+                bound,
+                best: best_move,
+            },
+        );
+    }
+
+    return best_score;
+}
+
+// keeps searching the moves that capture cards past the maximum depth so the static evaluation
+// is not taken in the middle of a capture exchange, a player may also settle for the evaluation
+fn quiescence(
+    board: &mut [[Option<Card>; 5]; 4],
+    deck1: &mut Vec<Card>,
+    deck2: &mut Vec<Card>,
+    bombs: &mut [[u8; 5]; 4],
+    player: u8,
+    mut alpha: i8,
+    mut beta: i8,
+    plies: u8,
+    context: &mut SearchContext,
+) -> i8 {
+    if context.stopped() {
+        return 0;
+    }
+
+    // if player 2 is out of cards, the game is over
+    if deck2.is_empty() {
+        let (p1_score, p2_score) = calc_scores(board);
+        if p1_score > p2_score {
+            return 100 + evaluation(board);
+        } else {
+            return -100 + evaluation(board);
+        }
+    }
+
+    let mut best_score = evaluation(board);
+    if plies == 0 {
+        return best_score;
+    }
+
+    // the evaluation alone may already be enough for a cutoff
+    if player == 1 {
+        if best_score >= beta {
+            return best_score;
+        }
+        alpha = max(alpha, best_score);
+    } else {
+        if best_score <= alpha {
+            return best_score;
+        }
+        beta = min(beta, best_score);
+    }
+
+    let mut moves = if player == 1 {
+        available_moves(board, deck1)
+    } else {
+        available_moves(board, deck2)
+    };
+    order_moves(
+        &mut moves,
+        board,
+        bombs,
+        if player == 1 { deck1 } else { deck2 },
+        player,
+        None,
+        context,
+    );
+
+    for m in 0..moves.len() {
+        let mut mov = moves[m];
+
+        let card = if player == 1 {
+            &deck1[mov.0]
+        } else {
+            &deck2[mov.0]
+        };
+        if !may_capture(board, card, &mov.3, player) {
+            continue;
+        }
+
+        // save the position to revert the move
+        let temp_board: [[Option<Card>; 5]; 4] = copy_board(board);
+        let temp_bombs = *bombs;
+        let temp_card = {
+            if player == 1 {
+                Card::copy(&deck1[mov.0])
+            } else {
+                Card::copy(&deck2[mov.0])
+            }
+        };
+
+        let outcome = Card::place_card(
+            board,
+            deck1,
+            deck2,
+            mov.0,
+            (mov.1, mov.2),
+            player,
+            bombs,
+            &mut mov.3,
+        )
+        .expect("Generated move is illegal !");
+
+        // only moves capturing cards (Same chains included) are searched
+        let score = if outcome.total_captures() > 0 {
+            context.stats.nodes += 1;
+            context.stats.quiescence_nodes += 1;
+            context.stats.ply += 1;
+            context.stats.max_depth = max(context.stats.max_depth, context.stats.ply);
+
+            let score = quiescence(
+                board,
+                deck1,
+                deck2,
+                bombs,
+                (player % 2) + 1,
+                alpha,
+                beta,
+                plies - 1,
+                context,
+            );
+
+            context.stats.ply -= 1;
+            Some(score)
+        } else {
+            None
+        };
+
+        // revert the move
+        if player == 1 {
+            deck1.insert(mov.0, temp_card);
+        } else {
+            deck2.insert(mov.0, temp_card);
+        }
+        *board = temp_board;
+        *bombs = temp_bombs;
+
+        if let Some(score) = score {
+            if player == 1 {
+                best_score = max(best_score, score);
+                alpha = max(alpha, best_score);
+                if best_score >= beta {
+                    context.stats.cutoffs += 1;
+                    break;
+                }
+            } else {
+                best_score = min(best_score, score);
+                beta = min(beta, best_score);
+                if best_score <= alpha {
+                    context.stats.cutoffs += 1;
+                    break;
+                }
             }
         }
     }
 
     return best_score;
 }
+
+// counts the positions reached after exactly depth moves, finished games count as reached positions
+pub fn perft(
+    board: &mut [[Option<Card>; 5]; 4],
+    deck1: &mut Vec<Card>,
+    deck2: &mut Vec<Card>,
+    bombs: &mut [[u8; 5]; 4],
+    player: u8,
+    depth: u8,
+) -> u64 {
+    if depth == 0 || deck2.is_empty() {
+        return 1;
+    }
+
+    return perft_divide(board, deck1, deck2, bombs, player, depth)
+        .iter()
+        .map(|(_, count)| count)
+        .sum();
+}
+
+// perft of every available move as ((card, row, column), count)
+pub fn perft_divide(
+    board: &mut [[Option<Card>; 5]; 4],
+    deck1: &mut Vec<Card>,
+    deck2: &mut Vec<Card>,
+    bombs: &mut [[u8; 5]; 4],
+    player: u8,
+    depth: u8,
+) -> Vec<((usize, usize, usize), u64)> {
+    // the position itself is the only one reached, there is no first move to break it down by
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    // every card is played, even identical ones, so the counts check the raw move generation
+    let moves = if player == 1 {
+        all_moves(board, deck1)
+    } else {
+        all_moves(board, deck2)
+    };
+
+    let mut counts = Vec::with_capacity(moves.len());
+    for m in 0..moves.len() {
+        let mut mov = moves[m];
+
+        // save the position to revert it after counting
+        let temp_board: [[Option<Card>; 5]; 4] = copy_board(board);
+        let temp_bombs = *bombs;
+        let temp_card = {
+            if player == 1 {
+                Card::copy(&deck1[mov.0])
+            } else {
+                Card::copy(&deck2[mov.0])
+            }
+        };
+
+        Card::place_card(
+            board,
+            deck1,
+            deck2,
+            mov.0,
+            (mov.1, mov.2),
+            player,
+            bombs,
+            &mut mov.3,
+        )
+        .expect("Generated move is illegal !");
+
+        let count = perft(board, deck1, deck2, bombs, (player % 2) + 1, depth - 1);
+        counts.push(((mov.0, mov.1, mov.2), count));
+
+        // revert the move
+        if player == 1 {
+            deck1.insert(mov.0, temp_card);
+        } else {
+            deck2.insert(mov.0, temp_card);
+        }
+        *board = temp_board;
+        *bombs = temp_bombs;
+    }
+
+    return counts;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // plain minimax without any pruning, used as the reference for the search
+    fn reference(
+        board: &mut [[Option<Card>; 5]; 4],
+        deck1: &[Card],
+        deck2: &[Card],
+        bombs: &mut [[u8; 5]; 4],
+        player: u8,
+        depth: u8,
+    ) -> i8 {
+        if deck2.is_empty() {
+            let (p1_score, p2_score) = calc_scores(board);
+            if p1_score > p2_score {
+                return 100 + evaluation(board);
+            } else {
+                return -100 + evaluation(board);
+            }
+        }
+        if depth == 0 {
+            return evaluation(board);
+        }
+
+        let deck_len = if player == 1 {
+            deck1.len()
+        } else {
+            deck2.len()
+        };
+        let mut scores = Vec::new();
+        for d in 0..deck_len {
+            for i in 0..4 {
+                for j in 0..5 {
+                    if board[i][j].is_some() {
+                        continue;
+                    }
+
+                    let mut t_board = copy_board(board);
+                    let mut t_bombs = *bombs;
+                    let mut t_deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+                    let mut t_deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
+                    let name = if player == 1 {
+                        deck1[d].name
+                    } else {
+                        deck2[d].name
+                    };
+                    let mut neighbours = Card::get_neighbours(&t_board, i, j, name);
+
+                    Card::place_card(
+                        &mut t_board,
+                        &mut t_deck1,
+                        &mut t_deck2,
+                        d,
+                        (i, j),
+                        player,
+                        &mut t_bombs,
+                        &mut neighbours,
+                    )
+                    .unwrap();
+
+                    scores.push(reference(
+                        &mut t_board,
+                        &t_deck1,
+                        &t_deck2,
+                        &mut t_bombs,
+                        (player % 2) + 1,
+                        depth - 1,
+                    ));
+                }
+            }
+        }
+
+        if player == 1 {
+            return *scores.iter().max().unwrap();
+        } else {
+            return *scores.iter().min().unwrap();
+        }
+    }
+
+    // a random position with the given number of cards left to play
+    fn endgame(
+        seed: u64,
+        cards_left: usize,
+    ) -> (
+        [[Option<Card>; 5]; 4],
+        Vec<Card>,
+        Vec<Card>,
+        [[u8; 5]; 4],
+        u8,
+    ) {
+        let mut rng = Rng::new(seed);
+
+        let mut deck1: Vec<Card> = Vec::new();
+        let mut deck2: Vec<Card> = Vec::new();
+        for d in 0..4 {
+            let unit = Unit::from_digit(rng.below(9) as u32 + 1).unwrap();
+            if d < 2 {
+                Card::add_to_deck(&mut deck1, unit, 1);
+            } else {
+                Card::add_to_deck(&mut deck2, unit, 2);
+            }
+        }
+
+        let mut board: [[Option<Card>; 5]; 4] = Default::default();
+        let mut bombs = [[0; 5]; 4];
+        let mut player = 1;
+
+        while deck1.len() + deck2.len() > cards_left {
+            let moves = available_moves(&board, if player == 1 { &deck1 } else { &deck2 });
+            let mut mov = moves[rng.below(moves.len())];
+            Card::place_card(
+                &mut board,
+                &mut deck1,
+                &mut deck2,
+                mov.0,
+                (mov.1, mov.2),
+                player,
+                &mut bombs,
+                &mut mov.3,
+            )
+            .unwrap();
+            player = (player % 2) + 1;
+        }
+
+        return (board, deck1, deck2, bombs, player);
+    }
+
+    #[test]
+    fn minimax_matches_reference_for_every_pruning_depth() {
+        for seed in 0..12 {
+            for cards_left in 1..=4 {
+                let (mut board, mut deck1, mut deck2, mut bombs, player) =
+                    endgame(seed, cards_left);
+
+                for depth in 1..=cards_left as u8 {
+                    let expected = reference(&mut board, &deck1, &deck2, &mut bombs, player, depth);
+
+                    for prune in 0..=depth {
+                        let score = minimax(
+                            &mut board,
+                            &mut deck1,
+                            &mut deck2,
+                            &mut bombs,
+                            player,
+                            -125,
+                            125,
+                            (depth, prune),
+                            &mut SearchContext::new(Arc::new(TranspositionTable::new(12))),
+                        );
+                        assert_eq!(
+                            score,
+                            expected,
+                            "seed {} cards left {} depth {:?}",
+                            seed,
+                            cards_left,
+                            (depth, prune)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ai_root_score_matches_reference() {
+        for seed in 0..12 {
+            for cards_left in 1..=4 {
+                let (mut board, deck1, deck2, mut bombs, player) = endgame(seed, cards_left);
+
+                let result = ai(
+                    &mut board,
+                    &deck1,
+                    &deck2,
+                    player,
+                    &mut bombs,
+                    &SearchOptions::default(),
+                    &Arc::new(TranspositionTable::new(12)),
+                );
+                let expected = reference(
+                    &mut board,
+                    &deck1,
+                    &deck2,
+                    &mut bombs,
+                    player,
+                    cards_left as u8,
+                );
+
+                assert_eq!(
+                    result.score, expected,
+                    "seed {} cards left {}",
+                    seed, cards_left
+                );
+
+                // the chosen move is one of the moves reaching that score
+                let (card, y, x, _) = result.best;
+                assert!(result.scores.contains(&((card, y, x), expected)));
+            }
+        }
+    }
+
+    // random decks with pairs of cards and their mirrors placed on mirrored cells
+    fn symmetric_position(
+        seed: u64,
+        pairs: usize,
+    ) -> ([[Option<Card>; 5]; 4], Vec<Card>, Vec<Card>, [[u8; 5]; 4]) {
+        let mut rng = Rng::new(seed);
+        let symmetry = (true, true);
+
+        let mut deck1: Vec<Card> = Vec::new();
+        let mut deck2: Vec<Card> = Vec::new();
+        for d in 0..4 {
+            let unit = Unit::from_digit(rng.below(9) as u32 + 1).unwrap();
+            if d < 2 {
+                Card::add_to_deck(&mut deck1, unit, 1);
+            } else {
+                Card::add_to_deck(&mut deck2, unit, 2);
+            }
+        }
+
+        let mut board: [[Option<Card>; 5]; 4] = Default::default();
+        let mut bombs = [[0; 5]; 4];
+        bombs[0][2] = 1;
+        bombs[3][2] = 1;
+
+        for p in 0..pairs * 2 {
+            let deck = if p % 2 == 0 { &mut deck1 } else { &mut deck2 };
+
+            // a card and its mirror which is the rotation 2 steps away
+            let card = deck.remove(rng.below(deck.len()));
+            let mirrored = mirror_card(&card, symmetry);
+            let m = deck.iter().position(|c| same_card(c, &mirrored)).unwrap();
+            deck.remove(m);
+
+            let empty: Vec<Position> = (0..4)
+                .flat_map(|i| (0..5).map(move |j| (i, j)))
+                .filter(|&(i, j)| board[i][j].is_none() && bombs[i][j] == 0)
+                .collect();
+            let (y, x) = empty[rng.below(empty.len())];
+            let (my, mx) = mirror_cell((y, x), symmetry);
+            board[y][x] = Some(card);
+            board[my][mx] = Some(mirrored);
+        }
+
+        return (board, deck1, deck2, bombs);
+    }
+
+    #[test]
+    fn identical_cards_are_played_once() {
+        let board: [[Option<Card>; 5]; 4] = Default::default();
+
+        let mut deck: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut deck, Unit::Warden, 1);
+        Card::add_to_deck(&mut deck, Unit::Warden, 1);
+        assert_eq!(available_moves(&board, &deck).len(), 4 * 20);
+
+        // lancers only have 2 different rotations
+        let mut deck: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut deck, Unit::Lancer, 2);
+        Card::add_to_deck(&mut deck, Unit::Titan, 2);
+        assert_eq!(available_moves(&board, &deck).len(), 6 * 20);
+        assert_eq!(all_moves(&board, &deck).len(), 8 * 20);
+    }
+
+    #[test]
+    fn symmetry_keeps_every_root_score() {
+        for seed in 0..12 {
+            let (mut board, deck1, deck2, mut bombs) = symmetric_position(seed, 3);
+            assert!(is_symmetric(&board, &bombs, &deck1, &deck2, (true, true)));
+
+            let mut options = SearchOptions {
+                depth: Some((4, 1)),
+                ..Default::default()
+            };
+            let tt = Arc::new(TranspositionTable::new(12));
+            let full = ai(&mut board, &deck1, &deck2, 1, &mut bombs, &options, &tt);
+            tt.clear();
+            options.symmetry = true;
+            let reduced = ai(&mut board, &deck1, &deck2, 1, &mut bombs, &options, &tt);
+
+            assert_eq!(reduced.score, full.score, "seed {}", seed);
+            assert!(reduced.stats.nodes < full.stats.nodes, "seed {}", seed);
+
+            let mut full_scores = full.scores.clone();
+            let mut reduced_scores = reduced.scores.clone();
+            full_scores.sort();
+            reduced_scores.sort();
+            assert_eq!(reduced_scores, full_scores, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn ordering_prefers_table_move_then_captures() {
+        let mut board: [[Option<Card>; 5]; 4] = Default::default();
+        let mut bombs = [[0; 5]; 4];
+        let mut deck: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut deck, Unit::Keeper, 1);
+
+        // a weak opponent card in the middle and a bomb in the corner
+        let mut weak: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut weak, Unit::Slayer, 2);
+        board[1][2] = Some(Card::copy(&weak[0]));
+        bombs[3][4] = 1;
+
+        let context = SearchContext::new(Arc::new(TranspositionTable::new(4)));
+        let mut moves = available_moves(&board, &deck);
+        order_moves(
+            &mut moves,
+            &board,
+            &bombs,
+            &deck,
+            1,
+            Some((2, 0, 0)),
+            &context,
+        );
+
+        assert_eq!((moves[0].0, moves[0].1, moves[0].2), (2, 0, 0));
+        let (d, y, x, neighbours) = moves[1];
+        assert!(capture_estimate(&board, &deck[d], &neighbours, 1) > 0);
+        assert_eq!((y, x), (0, 2));
+        let last = moves[moves.len() - 1];
+        assert_eq!((last.1, last.2), (3, 4));
+    }
+
+    #[test]
+    fn warm_table_gives_the_same_scores() {
+        for seed in 0..4 {
+            let (mut board, deck1, deck2, mut bombs, player) = endgame(seed, 5);
+            let tt = Arc::new(TranspositionTable::new(16));
+            let options = SearchOptions {
+                depth: Some((4, 2)),
+                ..Default::default()
+            };
+
+            let cold = ai(
+                &mut board, &deck1, &deck2, player, &mut bombs, &options, &tt,
+            );
+            let warm = ai(
+                &mut board, &deck1, &deck2, player, &mut bombs, &options, &tt,
+            );
+
+            let mut cold_scores = cold.scores.clone();
+            let mut warm_scores = warm.scores.clone();
+            cold_scores.sort();
+            warm_scores.sort();
+            assert_eq!(warm_scores, cold_scores, "seed {}", seed);
+            assert!(warm.stats.tt_hits > 0);
+            assert!(warm.stats.nodes < cold.stats.nodes);
+        }
+    }
+
+    #[test]
+    fn quiescence_settings_do_not_share_scores() {
+        let mut differs = false;
+        for seed in 0..4 {
+            let (mut board, deck1, deck2, mut bombs, player) = endgame(seed, 5);
+            let mut search = |quiescence: u8, tt: &Arc<TranspositionTable>| {
+                let options = SearchOptions {
+                    depth: Some((3, 2)),
+                    quiescence,
+                    ..Default::default()
+                };
+                let mut scores =
+                    ai(&mut board, &deck1, &deck2, player, &mut bombs, &options, tt).scores;
+                scores.sort();
+                return scores;
+            };
+
+            let fresh = search(0, &Arc::new(TranspositionTable::new(16)));
+            let tt = Arc::new(TranspositionTable::new(16));
+            let deeper = search(2, &tt);
+            assert_eq!(search(0, &tt), fresh, "seed {}", seed);
+            differs |= deeper != fresh;
+        }
+        assert!(differs);
+    }
+
+    #[test]
+    fn quiescence_settles_captures() {
+        for seed in 0..20 {
+            let (mut board, mut deck1, mut deck2, mut bombs, player) = endgame(seed, 8);
+            let deck = if player == 1 { &deck1 } else { &deck2 };
+
+            // best of the evaluation and every capturing move
+            let mut expected = evaluation(&board);
+            for (d, y, x, mut neighbours) in available_moves(&board, deck) {
+                let mut t_board = copy_board(&board);
+                let mut t_bombs = bombs;
+                let mut t_deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+                let mut t_deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
+                let outcome = Card::place_card(
+                    &mut t_board,
+                    &mut t_deck1,
+                    &mut t_deck2,
+                    d,
+                    (y, x),
+                    player,
+                    &mut t_bombs,
+                    &mut neighbours,
+                )
+                .unwrap();
+
+                if outcome.total_captures() > 0 {
+                    let score = evaluation(&t_board);
+                    expected = if player == 1 {
+                        max(expected, score)
+                    } else {
+                        min(expected, score)
+                    };
+                }
+            }
+
+            let mut context = SearchContext::new(Arc::new(TranspositionTable::new(4)));
+            let score = quiescence(
+                &mut board,
+                &mut deck1,
+                &mut deck2,
+                &mut bombs,
+                player,
+                -125,
+                125,
+                1,
+                &mut context,
+            );
+            assert_eq!(score, expected, "seed {}", seed);
+
+            // without plies left it is the static evaluation
+            let score = quiescence(
+                &mut board,
+                &mut deck1,
+                &mut deck2,
+                &mut bombs,
+                player,
+                -125,
+                125,
+                0,
+                &mut context,
+            );
+            assert_eq!(score, evaluation(&board));
+        }
+    }
+
+    #[test]
+    fn handle_reports_every_root_move() {
+        let (board, deck1, deck2, bombs, player) = endgame(3, 6);
+        let tt = Arc::new(TranspositionTable::new(12));
+        let options = SearchOptions {
+            depth: Some((2, 1)),
+            ..Default::default()
+        };
+
+        let search = SearchHandle::start(&board, &deck1, &deck2, player, &bombs, &options, &tt);
+        let reports: Vec<Progress> = search.progress().iter().collect();
+        let result = search.wait();
+
+        assert!(result.complete);
+        assert_eq!(reports.len(), result.scores.len());
+        for (i, progress) in reports.iter().enumerate() {
+            assert_eq!(progress.done, i + 1);
+            assert_eq!(progress.total, reports.len());
+        }
+        let (card, y, x, _) = result.best;
+        assert_eq!(
+            reports[reports.len() - 1].best,
+            ((card, y, x), result.score)
+        );
+    }
+
+    #[test]
+    fn stopped_search_keeps_the_moves_searched() {
+        // the start of a game takes far longer than the limit
+        let board: [[Option<Card>; 5]; 4] = Default::default();
+        let mut deck1: Vec<Card> = Vec::new();
+        let mut deck2: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut deck1, Unit::Warden, 1);
+        Card::add_to_deck(&mut deck1, Unit::Siren, 1);
+        Card::add_to_deck(&mut deck2, Unit::Titan, 2);
+        Card::add_to_deck(&mut deck2, Unit::Swarm, 2);
+        let tt = Arc::new(TranspositionTable::new(12));
+
+        let start = Instant::now();
+        let search = SearchHandle::start(
+            &board,
+            &deck1,
+            &deck2,
+            1,
+            &[[0; 5]; 4],
+            &SearchOptions::default(),
+            &tt,
+        );
+        let result = search.wait_for(Duration::from_millis(50));
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!result.complete);
+        assert!(result.scores.len() < 160);
+        let (card, y, x, _) = result.best;
+        assert!(result.scores.contains(&((card, y, x), result.score)));
+
+        // stopped before anything was searched, the first root move is still searched
+        let search = SearchHandle::start(
+            &board,
+            &deck1,
+            &deck2,
+            1,
+            &[[0; 5]; 4],
+            &SearchOptions {
+                depth: Some((2, 1)),
+                ..Default::default()
+            },
+            &tt,
+        );
+        search.stop();
+        let reports: Vec<Progress> = search.progress().iter().collect();
+        let result = search.wait();
+        assert!(!result.complete);
+        // moves cut short are not counted as done
+        assert_eq!(reports.last().unwrap().done, result.scores.len());
+        let (card, y, x, _) = result.best;
+        assert!(result.scores.contains(&((card, y, x), result.score)));
+        assert!(result.score.abs() <= 20);
+    }
+
+    #[test]
+    fn time_limit_is_enforced_by_the_search() {
+        // the start of a game takes far longer than the limit
+        let board: [[Option<Card>; 5]; 4] = Default::default();
+        let mut deck1: Vec<Card> = Vec::new();
+        let mut deck2: Vec<Card> = Vec::new();
+        Card::add_to_deck(&mut deck1, Unit::Keeper, 1);
+        Card::add_to_deck(&mut deck1, Unit::Ravager, 1);
+        Card::add_to_deck(&mut deck2, Unit::Slayer, 2);
+        Card::add_to_deck(&mut deck2, Unit::Swarm, 2);
+        let options = SearchOptions {
+            time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let result = ai(
+            &mut copy_board(&board),
+            &deck1,
+            &deck2,
+            1,
+            &mut [[0; 5]; 4],
+            &options,
+            &Arc::new(TranspositionTable::new(12)),
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!result.complete);
+        let (card, y, x, _) = result.best;
+        assert!(result.scores.contains(&((card, y, x), result.score)));
+
+        // a first root move far too deep to finish gives way to the shallow search
+        let options = SearchOptions {
+            depth: Some((12, 10)),
+            ..options
+        };
+        let start = Instant::now();
+        let result = ai(
+            &mut copy_board(&board),
+            &deck1,
+            &deck2,
+            1,
+            &mut [[0; 5]; 4],
+            &options,
+            &Arc::new(TranspositionTable::new(12)),
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!result.complete);
+        assert!(!result.scores.is_empty());
+        let (card, y, x, _) = result.best;
+        assert!(result.scores.contains(&((card, y, x), result.score)));
+    }
+
+    #[test]
+    fn ties_are_broken_the_same_way() {
+        let mut tied_positions = 0;
+        for seed in 0..6 {
+            let (mut board, deck1, deck2, mut bombs, player) = endgame(seed, 6);
+            let search = |options: &SearchOptions,
+                          board: &mut [[Option<Card>; 5]; 4],
+                          bombs: &mut [[u8; 5]; 4]| {
+                let tt = Arc::new(TranspositionTable::new(12));
+                let result = ai(board, &deck1, &deck2, player, bombs, options, &tt);
+                let (card, y, x, _) = result.best;
+                return ((card, y, x), result.score, result.scores);
+            };
+
+            let first = SearchOptions {
+                depth: Some((2, 1)),
+                ..Default::default()
+            };
+            let (best, score, scores) = search(&first, &mut board, &mut bombs);
+            let tied: Vec<(usize, usize, usize)> = scores
+                .iter()
+                .filter(|&&(_, s)| s == score)
+                .map(|&(mov, _)| mov)
+                .collect();
+            assert_eq!(best, tied[0], "seed {}", seed);
+            assert_eq!(
+                search(&first, &mut board, &mut bombs),
+                (best, score, scores)
+            );
+            if tied.len() > 1 {
+                tied_positions += 1;
+            }
+
+            // a seeded pick stays among the best moves and is the same every time
+            let seeded = SearchOptions {
+                seed: Some(seed),
+                ..first
+            };
+            let (pick, pick_score, _) = search(&seeded, &mut board, &mut bombs);
+            assert_eq!(pick_score, score);
+            assert!(tied.contains(&pick), "seed {}", seed);
+            assert_eq!(search(&seeded, &mut board, &mut bombs).0, pick);
+        }
+        assert!(tied_positions > 0);
+    }
+
+    #[test]
+    fn book_moves_are_played_without_searching() {
+        let (mut board, deck1, deck2, mut bombs, player) = endgame(2, 6);
+        let key = TranspositionTable::hash(&board, &bombs, &deck1, &deck2, player);
+        let tt = Arc::new(TranspositionTable::new(12));
+        let options = SearchOptions {
+            depth: Some((2, 1)),
+            ..Default::default()
+        };
+        let searched = ai(
+            &mut board, &deck1, &deck2, player, &mut bombs, &options, &tt,
+        );
+        let (card, y, x, _) = searched.best;
+
+        // the book is trusted even over a better move
+        let &(other, _) = searched
+            .scores
+            .iter()
+            .find(|&&(mov, _)| mov != (card, y, x))
+            .unwrap();
+        let mut book = OpeningBook::default();
+        book.insert(key, other, 42);
+        let with_book = SearchOptions {
+            book: Some(Arc::new(book)),
+            ..options.clone()
+        };
+        let result = ai(
+            &mut board, &deck1, &deck2, player, &mut bombs, &with_book, &tt,
+        );
+        let (card, y, x, _) = result.best;
+        assert_eq!(((card, y, x), result.score), (other, 42));
+        assert_eq!(result.scores, vec![(other, 42)]);
+        assert_eq!(result.stats.nodes, 0);
+
+        // a move that cannot be played here is left out
+        let mut book = OpeningBook::default();
+        book.insert(key, (deck1.len() + deck2.len(), 0, 0), 42);
+        let with_book = SearchOptions {
+            book: Some(Arc::new(book)),
+            ..options
+        };
+        let result = ai(
+            &mut board, &deck1, &deck2, player, &mut bombs, &with_book, &tt,
+        );
+        assert_eq!(result.best, searched.best);
+        assert_eq!(result.score, searched.score);
+    }
+}
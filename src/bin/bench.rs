@@ -0,0 +1,113 @@
+use std::{env, time::Duration};
+
+use legions_ai::{
+    ai::{SearchOptions, SearchStats},
+    card::{Position, Unit},
+    game::GameState,
+    rng::Rng,
+};
+
+// (seed, cards left) of every position in the suite
+const SUITE: [(u64, usize); 8] = [
+    (1, 14),
+    (2, 14),
+    (3, 12),
+    (4, 12),
+    (5, 10),
+    (6, 10),
+    (7, 8),
+    (8, 8),
+];
+
+// plays random moves from random decks until only the given number of cards are left
+fn position(seed: u64, cards_left: usize) -> GameState {
+    let mut rng = Rng::new(seed);
+
+    let mut units = [Unit::Warden; 4];
+    for unit in units.iter_mut() {
+        *unit = Unit::from_digit(rng.below(9) as u32 + 1).unwrap();
+    }
+    let mut game = GameState::new(units);
+
+    while game.deck1.len() + game.deck2.len() > cards_left {
+        let card = rng.below(game.deck(game.current_player()).len());
+        let empty: Vec<Position> = (0..4)
+            .flat_map(|i| (0..5).map(move |j| (i, j)))
+            .filter(|&(i, j)| game.board[i][j].is_none())
+            .collect();
+        let cell = empty[rng.below(empty.len())];
+        game.apply_move(card, cell).unwrap();
+    }
+
+    return game;
+}
+
+// runs the search on a fixed suite of positions: bench [depth] [pruning depth] [quiescence plies]
+fn main() {
+    let args: Vec<u8> = env::args()
+        .skip(1)
+        .map(|a| a.parse().expect("Depths must be numbers !"))
+        .collect();
+    let depth = (
+        args.first().copied().unwrap_or(3),
+        args.get(1).copied().unwrap_or(2),
+    );
+    let quiescence = args.get(2).copied().unwrap_or(0);
+
+    println!(
+        "Searching at depth {:?} with {} plies of quiescence\n",
+        depth, quiescence
+    );
+    println!(
+        "{:>3} {:>6} {:>6} {:>6} {:>12} {:>10} {:>10} {:>6} {:>10} {:>12}",
+        "#",
+        "cards",
+        "moves",
+        "score",
+        "nodes",
+        "cutoffs",
+        "tt hits",
+        "depth",
+        "time(ms)",
+        "nodes/s"
+    );
+
+    let mut total = SearchStats::default();
+    let mut elapsed = Duration::default();
+    for (i, &(seed, cards_left)) in SUITE.iter().enumerate() {
+        let mut game = position(seed, cards_left);
+        let result = game.best_move(&SearchOptions {
+            depth: Some(depth),
+            quiescence,
+            ..Default::default()
+        });
+
+        println!(
+            "{:>3} {:>6} {:>6} {:>6} {:>12} {:>10} {:>10} {:>6} {:>10} {:>12.0}",
+            i + 1,
+            cards_left,
+            result.scores.len(),
+            result.score,
+            result.stats.nodes,
+            result.stats.cutoffs,
+            result.stats.tt_hits,
+            result.stats.max_depth,
+            result.elapsed.as_millis(),
+            result.nodes_per_second()
+        );
+
+        total.merge(&result.stats);
+        elapsed += result.elapsed;
+    }
+
+    println!(
+        "\nTotal: {} nodes ({} in quiescence), {} cutoffs, {} tt hits, max depth {}, {} ms, {:.0} nodes/s",
+        total.nodes,
+        total.quiescence_nodes,
+        total.cutoffs,
+        total.tt_hits,
+        total.max_depth,
+        elapsed.as_millis(),
+        total.nodes as f64 / elapsed.as_secs_f64().max(1e-9)
+    );
+}
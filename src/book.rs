@@ -0,0 +1,183 @@
+use std::{collections::HashMap, fs, io};
+
+use crate::ai::SearchOptions;
+use crate::card::*;
+use crate::game::GameState;
+use crate::tt::TranspositionTable;
+
+// depth of the searches made for a book, one ply deeper than the live search of the opening
+pub const DEFAULT_DEPTH: (u8, u8) = (4, 3);
+
+// best moves of early positions found by long searches made ahead of time, keyed by the hash of
+// the position which covers the board, bombs, both decks and the player to move
+#[derive(Clone, Default, Debug)]
+pub struct OpeningBook {
+    // hash of the position -> (best move as (card, row, column), its score)
+    entries: HashMap<u64, ((usize, usize, usize), i8)>,
+}
+
+impl OpeningBook {
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    pub fn probe(&self, key: u64) -> Option<((usize, usize, usize), i8)> {
+        return self.entries.get(&key).copied();
+    }
+
+    pub fn insert(&mut self, key: u64, mov: (usize, usize, usize), score: i8) {
+        self.entries.insert(key, (mov, score));
+    }
+
+    // one entry per line as "hash card row column score", lines starting with # are comments
+    pub fn parse(text: &str) -> Result<OpeningBook, String> {
+        let mut book = OpeningBook::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad_line = || format!("Bad opening book entry on line {}", i + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err(bad_line());
+            }
+            let key = u64::from_str_radix(fields[0], 16).map_err(|_| bad_line())?;
+            let numbers: Vec<usize> = fields[1..4]
+                .iter()
+                .map(|f| f.parse().ok())
+                .collect::<Option<Vec<usize>>>()
+                .ok_or_else(bad_line)?;
+            let score: i8 = fields[4].parse().map_err(|_| bad_line())?;
+            if numbers[1] >= 4 || numbers[2] >= 5 {
+                return Err(bad_line());
+            }
+
+            book.insert(key, (numbers[0], numbers[1], numbers[2]), score);
+        }
+        return Ok(book);
+    }
+
+    pub fn load(path: &str) -> Result<OpeningBook, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        return OpeningBook::parse(&text);
+    }
+
+    // the entries sorted by hash so the same book is always written the same way
+    pub fn to_text(&self) -> String {
+        let mut entries: Vec<(&u64, &((usize, usize, usize), i8))> = self.entries.iter().collect();
+        entries.sort();
+
+        let mut text = String::from("# legions opening book: hash card row column score\n");
+        for (key, ((card, y, x), score)) in entries {
+            text += &format!("{:016x} {} {} {} {}\n", key, card, y, x, score);
+        }
+        return text;
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        return fs::write(path, self.to_text());
+    }
+
+    // searches the start of a game with the given decks: the best move of each position is
+    // stored, then the positions after the `width` best moves are searched until `plies` moves
+    // were played. the options should not use a book themselves
+    pub fn generate(&mut self, units: [Unit; 4], plies: u8, width: usize, options: &SearchOptions) {
+        let mut game = GameState::new(units);
+        self.expand(&mut game, plies, width, options);
+    }
+
+    fn expand(&mut self, game: &mut GameState, plies: u8, width: usize, options: &SearchOptions) {
+        if plies == 0 || game.is_over() {
+            return;
+        }
+
+        let player = game.current_player();
+        let key =
+            TranspositionTable::hash(&game.board, &game.bombs, &game.deck1, &game.deck2, player);
+        let result = game.best_move(options);
+        let (card, y, x, _) = result.best;
+        self.insert(key, (card, y, x), result.score);
+
+        // the most likely moves are followed, best first
+        let mut scores = result.scores;
+        scores.sort_by_key(|&(mov, score)| (if player == 1 { -score } else { score }, mov));
+        for &((card, y, x), _) in scores.iter().take(width) {
+            game.apply_move(card, (y, x))
+                .expect("Searched move is illegal !");
+            self.expand(game, plies - 1, width, options);
+            game.undo();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn text_round_trip() {
+        let mut book = OpeningBook::default();
+        book.insert(0xdead_beef, (7, 3, 4), -12);
+        book.insert(u64::MAX, (0, 0, 0), 105);
+
+        let text = book.to_text();
+        let parsed = OpeningBook::parse(&text).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.probe(0xdead_beef), Some(((7, 3, 4), -12)));
+        assert_eq!(parsed.probe(u64::MAX), Some(((0, 0, 0), 105)));
+        assert_eq!(parsed.to_text(), text);
+
+        assert!(OpeningBook::parse("# only comments\n\n")
+            .unwrap()
+            .is_empty());
+        assert!(OpeningBook::parse("12 1 2 3").is_err());
+        assert!(OpeningBook::parse("xyz 1 2 3 4").is_err());
+        assert!(OpeningBook::parse("12 1 4 0 4").is_err());
+    }
+
+    #[test]
+    fn generated_book_holds_the_searched_moves() {
+        let options = SearchOptions {
+            depth: Some((1, 0)),
+            ..Default::default()
+        };
+        let units = [Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Warden];
+        let mut book = OpeningBook::default();
+        book.generate(units, 2, 3, &options);
+        // the start and the positions after the 3 best moves
+        assert_eq!(book.len(), 4);
+
+        let mut game = GameState::new(units);
+        let key = TranspositionTable::hash(&game.board, &game.bombs, &game.deck1, &game.deck2, 1);
+        let result = game.best_move(&options);
+        let (card, y, x, _) = result.best;
+        assert_eq!(book.probe(key), Some(((card, y, x), result.score)));
+    }
+
+    #[test]
+    fn time_limited_generation_stores_legal_moves() {
+        let options = SearchOptions {
+            depth: Some(DEFAULT_DEPTH),
+            time: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let units = [Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Warden];
+        let mut book = OpeningBook::default();
+        book.generate(units, 2, 2, &options);
+
+        // the searches were cut short but each one finished a move, which is followed
+        assert!(book.len() >= 2);
+        let mut game = GameState::new(units);
+        let key = TranspositionTable::hash(&game.board, &game.bombs, &game.deck1, &game.deck2, 1);
+        let ((card, y, x), _) = book.probe(key).unwrap();
+        assert!(game.apply_move(card, (y, x)).is_ok());
+    }
+}
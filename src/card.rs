@@ -4,6 +4,8 @@ use std::{
     ops::Range,
 };
 
+use crate::error::PlaceError;
+
 pub type Position = (usize, usize);
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -27,6 +29,184 @@ pub enum Unit {
     Lancer,
 }
 
+impl Unit {
+    // maps the unit type digit used in deck inputs (1 to 9) to a unit
+    pub fn from_digit(digit: u32) -> Option<Unit> {
+        match digit {
+            1 => Some(Unit::Warden),
+            2 => Some(Unit::Keeper),
+            3 => Some(Unit::Siren),
+            4 => Some(Unit::Saboteur),
+            5 => Some(Unit::Ravager),
+            6 => Some(Unit::Titan),
+            7 => Some(Unit::Slayer),
+            8 => Some(Unit::Swarm),
+            9 => Some(Unit::Lancer),
+            _ => None,
+        }
+    }
+}
+
+// result of the fight between two cards
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FightResult {
+    Win,
+    Tie,
+    Lose,
+}
+
+// how a card was captured
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CaptureKind {
+    Normal,
+    Same,
+    Combo,
+    Pierce,
+}
+
+// a single step of what happened when a card was placed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    PlacedCard {
+        cell: Position,
+        unit: Unit,
+        player: u8,
+    },
+    BombDetonated {
+        cell: Position,
+        damage: u8,
+    },
+    Pulled {
+        from: Position,
+        to: Position,
+        damage: u8,
+    },
+    Flipped {
+        cell: Position,
+    },
+    // values are the attack and defense values after bonuses
+    Battle {
+        attacker: Position,
+        defender: Position,
+        values: (u8, u8),
+        result: FightResult,
+    },
+    Captured {
+        cell: Position,
+        player: u8,
+        via: CaptureKind,
+    },
+    RavagerUpgraded {
+        cell: Position,
+    },
+}
+
+// what happened when a card was placed
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct MoveOutcome {
+    // cards captured by winning a battle
+    pub captures: u8,
+    // cards captured through the Same mechanic
+    pub same_captures: u8,
+    // cards captured by cards that were captured through Same
+    pub combo_captures: u8,
+    // cards captured by Lancer's pierce
+    pub pierce_captures: u8,
+    // cards pulled by a Siren
+    pub pulls: u8,
+    // cards flipped by a Titan
+    pub flips: u8,
+    // bombs detonated on the placed card or on pulled cards
+    pub bomb_damage: u8,
+    // every step in order, only recorded when requested since the search has no use for it
+    pub events: Option<Vec<Event>>,
+}
+
+impl MoveOutcome {
+    // an outcome that records the events
+    pub fn narrated() -> MoveOutcome {
+        return MoveOutcome {
+            events: Some(Vec::new()),
+            ..Default::default()
+        };
+    }
+
+    fn record(&mut self, event: Event) {
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+    }
+
+    // cards that changed owner
+    pub fn total_captures(&self) -> u8 {
+        return self.captures + self.same_captures + self.combo_captures + self.pierce_captures;
+    }
+
+    // what happened to each cell of the board, found from the events
+    pub fn changes(&self) -> [[CellChange; 5]; 4] {
+        let mut changes: [[CellChange; 5]; 4] = Default::default();
+        for event in self.events.iter().flatten() {
+            match *event {
+                Event::PlacedCard { cell, .. } => changes[cell.0][cell.1].placed = true,
+                Event::BombDetonated { cell, .. } => changes[cell.0][cell.1].damaged = true,
+                // what happened to the card so far goes with it
+                Event::Pulled { from, to, damage } => {
+                    let mut change = changes[from.0][from.1];
+                    changes[from.0][from.1] = CellChange::default();
+                    change.moved = true;
+                    change.damaged |= damage > 0;
+                    changes[to.0][to.1] = change;
+                }
+                Event::Flipped { cell } => changes[cell.0][cell.1].flipped = true,
+                Event::Captured { cell, .. } => changes[cell.0][cell.1].captured = true,
+                Event::Battle { .. } | Event::RavagerUpgraded { .. } => {}
+            }
+        }
+        return changes;
+    }
+}
+
+// how a cell changed during a move
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct CellChange {
+    pub placed: bool,
+    pub captured: bool,
+    // pulled here by a Siren
+    pub moved: bool,
+    // values flipped by a Titan
+    pub flipped: bool,
+    // values lowered by bombs
+    pub damaged: bool,
+}
+
+impl CellChange {
+    // (marker, meaning) of every kind of change
+    pub const LEGEND: [(char, &'static str); 5] = [
+        ('+', "placed"),
+        ('C', "captured"),
+        ('M', "pulled here"),
+        ('F', "flipped"),
+        ('D', "damaged by bombs"),
+    ];
+
+    // markers of the changes that happened, empty if the cell did not change
+    pub fn markers(&self) -> String {
+        let flags = [
+            self.placed,
+            self.captured,
+            self.moved,
+            self.flipped,
+            self.damaged,
+        ];
+        return CellChange::LEGEND
+            .iter()
+            .zip(flags)
+            .filter(|(_, flag)| *flag)
+            .map(|((marker, _), _)| *marker)
+            .collect();
+    }
+}
+
 #[derive(Debug)]
 pub struct Card {
     pub name: Unit,
@@ -119,6 +299,7 @@ impl Card {
         position: Position,
         neighbours: &mut [Option<Position>; 4],
         bombs: &mut [[u8; 5]; 4],
+        outcome: &mut MoveOutcome,
     ) {
         let (y, x) = position;
         let directions = [
@@ -160,6 +341,7 @@ impl Card {
 
                 // apply damage
                 neighbour.as_mut().unwrap().downgrade(damage);
+                outcome.bomb_damage += damage;
 
                 // relocate card
                 match direction {
@@ -180,14 +362,30 @@ impl Card {
                         neighbours[i] = Some((y, x - 1));
                     }
                 }
+                // adjacent cards stay where they are
+                let pulled_to = neighbours[i].unwrap();
+                if pulled_to != (ny, nx) {
+                    outcome.pulls += 1;
+                    outcome.record(Event::Pulled {
+                        from: (ny, nx),
+                        to: pulled_to,
+                        damage,
+                    });
+                }
             }
         }
     }
 
     // flip the facing value with the value of the other end
-    pub fn flip(board: &mut [[Option<Card>; 5]; 4], neighbours: [Option<Position>; 4]) {
+    pub fn flip(
+        board: &mut [[Option<Card>; 5]; 4],
+        neighbours: [Option<Position>; 4],
+        outcome: &mut MoveOutcome,
+    ) {
         for i in 0..4 {
             if let Some(neighbour) = neighbours[i] {
+                outcome.flips += 1;
+                outcome.record(Event::Flipped { cell: neighbour });
                 let n = board[neighbour.0][neighbour.1].as_mut().unwrap();
                 match i {
                     // top & bottom
@@ -392,7 +590,27 @@ impl Card {
         return neighbours;
     }
 
-    // places a card out of the player's deck on to the board. returns success
+    // checks that the card exists in the deck and the cell is free on the board
+    pub fn check_move(
+        board: &[[Option<Card>; 5]; 4],
+        deck: &[Card],
+        card: usize,
+        mov: Position,
+    ) -> Result<(), PlaceError> {
+        if card >= deck.len() {
+            return Err(PlaceError::CardIndexOutOfRange(card));
+        }
+        if mov.0 >= 4 || mov.1 >= 5 {
+            return Err(PlaceError::CellOutOfBounds(mov));
+        }
+        if let Some(c) = &board[mov.0][mov.1] {
+            return Err(PlaceError::CellOccupied(mov, c.name));
+        }
+
+        return Ok(());
+    }
+
+    // places a card out of the player's deck on to the board if the move is legal, returning what happened
     pub fn place_card(
         board: &mut [[Option<Card>; 5]; 4],
         deck1: &mut Vec<Card>,
@@ -402,38 +620,73 @@ impl Card {
         player: u8,
         bombs: &mut [[u8; 5]; 4],
         neighbours: &mut [Option<Position>; 4],
-    ) -> bool {
-        // determine the cell on the board
-        let cell = &mut board[mov.0][mov.1];
-
-        // replace cell placeholder card if move is legal
-        match cell {
-            // there is already a card in that cell
-            Some(c) => {
-                println!(
-                    "{}-{} is already occupied with a {:?} !",
-                    mov.0, mov.1, c.name
-                );
-                return false;
-            }
-            // cell is free to place a card
-            None => {
-                // player 1 plays
-                if player == 1 {
-                    *cell = Some(deck1.remove(card));
-                }
-                // player 2 plays
-                else {
-                    *cell = Some(deck2.remove(card));
-                }
+    ) -> Result<MoveOutcome, PlaceError> {
+        return Card::place(
+            board,
+            deck1,
+            deck2,
+            card,
+            mov,
+            player,
+            bombs,
+            neighbours,
+            MoveOutcome::default(),
+        );
+    }
 
-                Card::placement(board, mov, bombs, neighbours);
+    // same as place_card but the outcome also holds every event of the move
+    pub fn place_card_narrated(
+        board: &mut [[Option<Card>; 5]; 4],
+        deck1: &mut Vec<Card>,
+        deck2: &mut Vec<Card>,
+        card: usize,
+        mov: Position,
+        player: u8,
+        bombs: &mut [[u8; 5]; 4],
+        neighbours: &mut [Option<Position>; 4],
+    ) -> Result<MoveOutcome, PlaceError> {
+        return Card::place(
+            board,
+            deck1,
+            deck2,
+            card,
+            mov,
+            player,
+            bombs,
+            neighbours,
+            MoveOutcome::narrated(),
+        );
+    }
+
+    fn place(
+        board: &mut [[Option<Card>; 5]; 4],
+        deck1: &mut Vec<Card>,
+        deck2: &mut Vec<Card>,
+        card: usize,
+        mov: Position,
+        player: u8,
+        bombs: &mut [[u8; 5]; 4],
+        neighbours: &mut [Option<Position>; 4],
+        mut outcome: MoveOutcome,
+    ) -> Result<MoveOutcome, PlaceError> {
+        let deck = if player == 1 { deck1 } else { deck2 };
 
-                Card::play(board, mov.0, mov.1, false, Some(*neighbours));
+        Card::check_move(board, deck, card, mov)?;
 
-                return true;
-            }
-        }
+        // cell is free to place the player's card
+        let card = deck.remove(card);
+        outcome.record(Event::PlacedCard {
+            cell: mov,
+            unit: card.name,
+            player,
+        });
+        board[mov.0][mov.1] = Some(card);
+
+        Card::placement(board, mov, bombs, neighbours, &mut outcome);
+
+        Card::play(board, mov.0, mov.1, false, Some(*neighbours), &mut outcome);
+
+        return Ok(outcome);
     }
 
     // placement event that needs to be run after card is placed on the board for the first time
@@ -442,6 +695,7 @@ impl Card {
         position: Position,
         bombs: &mut [[u8; 5]; 4],
         neighbours: &mut [Option<Position>; 4],
+        outcome: &mut MoveOutcome,
     ) {
         let y = position.0;
         let x = position.1;
@@ -468,18 +722,18 @@ impl Card {
             }
             // Siren pulls cards
             Unit::Siren => {
-                Card::pull(board, position, neighbours, bombs);
+                Card::pull(board, position, neighbours, bombs, outcome);
             }
             // Titan flips adjacent cards
             Unit::Titan => {
-                Card::flip(board, *neighbours);
+                Card::flip(board, *neighbours, outcome);
             }
             // Others do nothing at this stage
             _ => {}
         }
 
         // after card is placed, check for bombs
-        Card::bomb_check(board, position, bombs);
+        Card::bomb_check(board, position, bombs, outcome);
     }
 
     // checks for bombs and applies damage accordingly
@@ -487,18 +741,21 @@ impl Card {
         board: &mut [[Option<Card>; 5]; 4],
         position: Position,
         bombs: &mut [[u8; 5]; 4],
+        outcome: &mut MoveOutcome,
     ) {
         let cell = &mut bombs[position.0][position.1];
         // check if there is a bomb
         if *cell > 0 {
-            match &mut board[position.0][position.1] {
-                Some(card) => {
-                    // bombs detonate reducing every stat down to a minimum of 1
-                    card.downgrade(*cell);
-                    // all bombs are detonated
-                    *cell = 0;
-                }
-                None => {}
+            if let Some(card) = &mut board[position.0][position.1] {
+                // bombs detonate reducing every stat down to a minimum of 1
+                card.downgrade(*cell);
+                outcome.bomb_damage += *cell;
+                outcome.record(Event::BombDetonated {
+                    cell: position,
+                    damage: *cell,
+                });
+                // all bombs are detonated
+                *cell = 0;
             }
         }
     }
@@ -510,21 +767,15 @@ impl Card {
         x: usize,
         combo: bool,
         neighbours: Option<[Option<Position>; 4]>,
+        outcome: &mut MoveOutcome,
     ) {
-        // result of the fight between two cards
-        #[derive(Clone, Copy, PartialEq, Debug)]
-        enum FightResult {
-            Win,
-            Tie,
-            Lose,
-        }
-
         // handles battle with specified neighbour and returns the result
         fn battle(
             board: &mut [[Option<Card>; 5]; 4],
             position: Position,
             neighbour_position: Position,
             direction: Direction,
+            outcome: &mut MoveOutcome,
         ) -> Option<FightResult> {
             let (y, x) = position;
             let (ny, nx) = neighbour_position;
@@ -539,59 +790,38 @@ impl Card {
                 // fetch defender
                 let d = neighbour;
 
-                match direction {
-                    Direction::Top => {
-                        return Some(fight(
-                            a.name,
-                            d.name,
-                            a.top,
-                            d.bottom,
-                            board,
-                            attacking_player,
-                            d.player,
-                        ));
-                    }
-                    Direction::Right => {
-                        return Some(fight(
-                            a.name,
-                            d.name,
-                            a.right,
-                            d.left,
-                            board,
-                            attacking_player,
-                            d.player,
-                        ));
-                    }
-                    Direction::Bottom => {
-                        return Some(fight(
-                            a.name,
-                            d.name,
-                            a.bottom,
-                            d.top,
-                            board,
-                            attacking_player,
-                            d.player,
-                        ));
-                    }
-                    Direction::Left => {
-                        return Some(fight(
-                            a.name,
-                            d.name,
-                            a.left,
-                            d.right,
-                            board,
-                            attacking_player,
-                            d.player,
-                        ));
-                    }
-                }
+                // the facing values of both cards
+                let (attack_value, defense_value) = match direction {
+                    Direction::Top => (a.top, d.bottom),
+                    Direction::Right => (a.right, d.left),
+                    Direction::Bottom => (a.bottom, d.top),
+                    Direction::Left => (a.left, d.right),
+                };
+
+                let (result, attack_value, defense_value) = fight(
+                    a.name,
+                    d.name,
+                    attack_value,
+                    defense_value,
+                    board,
+                    attacking_player,
+                    d.player,
+                );
+                outcome.record(Event::Battle {
+                    attacker: position,
+                    defender: neighbour_position,
+                    values: (attack_value, defense_value),
+                    result,
+                });
+
+                return Some(result);
             }
 
             // if neighbour is empty or no fight was fought, return None
             return None;
         }
 
-        // fight the opponent card and returns result
+        // fight the opponent card and returns result with the final attack and defense values
         fn fight(
             attacker: Unit,
             defender: Unit,
@@ -600,13 +830,11 @@ impl Card {
             board: &[[Option<Card>; 5]; 4],
             attacking_player: u8,
             defending_player: u8,
-        ) -> FightResult {
+        ) -> (FightResult, u8, u8) {
             match defender {
                 // Warden has a defense bonus ONLY against enemies
-                Unit::Warden => {
-                    if attacking_player != defending_player {
-                        defense_value += 1;
-                    }
+                Unit::Warden if attacking_player != defending_player => {
+                    defense_value += 1;
                 }
                 // Swarm gets ally bonus
                 Unit::Swarm => {
@@ -637,13 +865,13 @@ impl Card {
             // do the battle
             if attack_value > defense_value {
                 // attacker wins
-                return FightResult::Win;
+                return (FightResult::Win, attack_value, defense_value);
             } else if attack_value == defense_value {
                 // it's a tie
-                return FightResult::Tie;
+                return (FightResult::Tie, attack_value, defense_value);
             } else {
                 // attacker has lost the battle
-                return FightResult::Lose;
+                return (FightResult::Lose, attack_value, defense_value);
             }
         }
 
@@ -657,6 +885,7 @@ impl Card {
             combo: bool,
             direction: Direction,
             pierce: bool,
+            outcome: &mut MoveOutcome,
         ) {
             // println!(
             //     "Handling a {:?} @ {}, {} vs {}, {} towards {:?} with same_count = {}, combo = {}, pierce = {}",
@@ -673,7 +902,15 @@ impl Card {
             match result {
                 FightResult::Win => {
                     // capture neighbour when the battle is won
-                    let captured = capture_event(neighbour_position, position, board, combo);
+                    let via = if pierce {
+                        CaptureKind::Pierce
+                    } else if combo {
+                        CaptureKind::Combo
+                    } else {
+                        CaptureKind::Normal
+                    };
+                    let captured =
+                        capture_event(neighbour_position, position, board, combo, via, outcome);
 
                     if !captured {
                         return;
@@ -694,13 +931,14 @@ impl Card {
                             board[ny][nx] = board[y][x].take();
 
                             // commence battle at the neighbour's position
-                            let battle_result = battle(board, neighbour_position, d, direction);
+                            let battle_result =
+                                battle(board, neighbour_position, d, direction, outcome);
 
                             // check if there was a battle
-                            if battle_result.is_some() {
+                            if let Some(battle_result) = battle_result {
                                 // handle the result
                                 handle_result(
-                                    battle_result.unwrap(),
+                                    battle_result,
                                     0,
                                     neighbour_position,
                                     d,
@@ -708,6 +946,7 @@ impl Card {
                                     combo,
                                     direction,
                                     true,
+                                    outcome,
                                 );
                             }
 
@@ -721,7 +960,14 @@ impl Card {
                     // if more than one neighbours have same values, it's a valid capture
                     if same > 1 {
                         // a same capture starts a Same Chain
-                        capture_event(neighbour_position, position, board, true);
+                        capture_event(
+                            neighbour_position,
+                            position,
+                            board,
+                            true,
+                            CaptureKind::Same,
+                            outcome,
+                        );
                     }
                 }
                 FightResult::Lose => {}
@@ -734,6 +980,8 @@ impl Card {
             attacker_position: Position,
             board: &mut [[Option<Card>; 5]; 4],
             combo: bool,
+            via: CaptureKind,
+            outcome: &mut MoveOutcome,
         ) -> bool {
             // determine the attacking player
             let attacking_player = board[attacker_position.0][attacker_position.1]
@@ -755,14 +1003,35 @@ impl Card {
                 .unwrap();
             // change owner of the captured card
             defender.player = attacking_player;
+            match via {
+                CaptureKind::Normal => outcome.captures += 1,
+                CaptureKind::Same => outcome.same_captures += 1,
+                CaptureKind::Combo => outcome.combo_captures += 1,
+                CaptureKind::Pierce => outcome.pierce_captures += 1,
+            }
+            outcome.record(Event::Captured {
+                cell: defender_position,
+                player: attacking_player,
+                via,
+            });
             // Ravager gets an upgrade upon being captured
             if defender.name == Unit::Ravager {
                 defender.upgrade(1);
+                outcome.record(Event::RavagerUpgraded {
+                    cell: defender_position,
+                });
             }
 
             // if this card was captured through the "Same" mechanic, it gets played by it's new owner
             if combo {
-                Card::play(board, defender_position.0, defender_position.1, combo, None);
+                Card::play(
+                    board,
+                    defender_position.0,
+                    defender_position.1,
+                    combo,
+                    None,
+                    outcome,
+                );
             }
 
             // update attacker
@@ -772,6 +1041,9 @@ impl Card {
             // Ravager gets an upgrade upon capturing
             if attacker.name == Unit::Ravager {
                 attacker.upgrade(1);
+                outcome.record(Event::RavagerUpgraded {
+                    cell: attacker_position,
+                });
             }
 
             true
@@ -787,22 +1059,22 @@ impl Card {
 
         // handle the battle with top neighbour
         let top_battle = match neighbours[0] {
-            Some(n) => battle(board, (y, x), n, Direction::Top),
+            Some(n) => battle(board, (y, x), n, Direction::Top, outcome),
             None => None,
         };
         // handle the battle with right neighbour
         let right_battle = match neighbours[1] {
-            Some(n) => battle(board, (y, x), n, Direction::Right),
+            Some(n) => battle(board, (y, x), n, Direction::Right, outcome),
             None => None,
         };
         // handle the battle with the bottom neighbour
         let bottom_battle = match neighbours[2] {
-            Some(n) => battle(board, (y, x), n, Direction::Bottom),
+            Some(n) => battle(board, (y, x), n, Direction::Bottom, outcome),
             None => None,
         };
         // handle the battle with the left neighbour
         let left_battle = match neighbours[3] {
-            Some(n) => battle(board, (y, x), n, Direction::Left),
+            Some(n) => battle(board, (y, x), n, Direction::Left, outcome),
             None => None,
         };
 
@@ -842,6 +1114,7 @@ impl Card {
                 combo,
                 Direction::Top,
                 false,
+                outcome,
             );
         }
         // handle result of right battle
@@ -855,6 +1128,7 @@ impl Card {
                 combo,
                 Direction::Right,
                 false,
+                outcome,
             );
         }
         // handle result of bottom battle
@@ -868,6 +1142,7 @@ impl Card {
                 combo,
                 Direction::Bottom,
                 false,
+                outcome,
             );
         }
         // handle result of left battle
@@ -881,7 +1156,80 @@ impl Card {
                 combo,
                 Direction::Left,
                 false,
+                outcome,
             );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(card: &Card) -> [u8; 4] {
+        return [card.top, card.right, card.bottom, card.left];
+    }
+
+    #[test]
+    fn upgrade_caps_at_ten() {
+        let mut card = Card {
+            name: Unit::Ravager,
+            top: 8,
+            right: 4,
+            bottom: 10,
+            left: 9,
+            player: 1,
+        };
+        card.upgrade(2);
+        assert_eq!(values(&card), [10, 6, 10, 10]);
+    }
+
+    #[test]
+    fn downgrade_stops_at_one() {
+        let mut card = Card {
+            name: Unit::Keeper,
+            top: 9,
+            right: 5,
+            bottom: 1,
+            left: 3,
+            player: 1,
+        };
+        card.downgrade(3);
+        assert_eq!(values(&card), [6, 2, 1, 1]);
+    }
+
+    #[test]
+    fn player1_deck_holds_every_rotation() {
+        let mut deck = Vec::new();
+        Card::add_to_deck(&mut deck, Unit::Titan, 1);
+
+        let rotations: Vec<[u8; 4]> = deck.iter().map(values).collect();
+        assert_eq!(
+            rotations,
+            vec![[7, 4, 6, 3], [3, 7, 4, 6], [6, 3, 7, 4], [4, 6, 3, 7]]
+        );
+        assert!(deck.iter().all(|c| c.player == 1 && c.name == Unit::Titan));
+    }
+
+    #[test]
+    fn player2_deck_is_mirrored() {
+        let mut deck = Vec::new();
+        Card::add_to_deck(&mut deck, Unit::Titan, 2);
+
+        let rotations: Vec<[u8; 4]> = deck.iter().map(values).collect();
+        assert_eq!(
+            rotations,
+            vec![[7, 3, 6, 4], [4, 7, 3, 6], [6, 4, 7, 3], [3, 6, 4, 7]]
+        );
+        assert!(deck.iter().all(|c| c.player == 2));
+    }
+
+    #[test]
+    fn unit_digits_match_the_deck_prompt() {
+        assert_eq!(Unit::from_digit(1), Some(Unit::Warden));
+        assert_eq!(Unit::from_digit(5), Some(Unit::Ravager));
+        assert_eq!(Unit::from_digit(9), Some(Unit::Lancer));
+        assert_eq!(Unit::from_digit(0), None);
+        assert_eq!(Unit::from_digit(10), None);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::ai::SearchOptions;
+use crate::book::OpeningBook;
+use crate::card::*;
+use crate::difficulty::Difficulty;
+use crate::utility::*;
+
+pub const USAGE: &str = "Usage: legions_ai [COMMAND] [OPTIONS]
+
+Commands:
+  play                  play a game in the terminal (the default)
+  tui                   play a game in full screen
+  analyze [MOVES]       score every move of the position reached after the moves
+  arena                 play AI against AI and count the wins
+  replay [MOVES]        show every position of a game and what each move did
+  serve [ADDRESS]       serve games over HTTP (0.0.0.0:8080 by default)
+  json                  read JSON requests from stdin, one per line
+  perft DEPTH           count the positions reached after DEPTH moves
+  book PATH [PLIES] [WIDTH]
+                        search the first PLIES moves (4 by default) following the
+                        WIDTH best moves (3 by default) and save them as an opening book
+
+Moves are written as CardIndexYX like in the game, e.g. 111 234
+
+Options:
+  --decks A B C D       unit types of the players (player1 player1 player2 player2)
+  --ai-plays SEAT       seat played by the AI on its own: blue, red, both or none
+  --difficulty LEVEL    beginner, easy, medium or hard (or 1 to 4)
+  --depth D[,P]         fixed search depth and pruning depth (P defaults to D - 1)
+  --time SECONDS        time limit of each search
+  --threads N           search threads (one per core by default)
+  --quiescence PLIES    plies of captures searched past the depth (0, off, by default)
+  --rules RULES         rule set, only standard is supported
+  --blue PLAYER         who plays Blue: human or an AI difficulty
+  --red PLAYER          who plays Red: human or an AI difficulty
+  --delay SECONDS       pause after each move of an AI seat
+  --games N             games played in the arena (10 by default)
+  --seed N              seed of the random decks of the arena and of weaker levels
+  --file PATH           read the moves to analyze or replay from a file
+  --html PATH           save the replayed game as an HTML page
+  --book PATH           play the moves of an opening book at full strength
+  -h, --help            show this help";
+
+// who makes the moves of a seat
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Controller {
+    Human,
+    Ai(Difficulty),
+}
+
+impl Controller {
+    // human, or an AI difficulty by name or number
+    pub fn from_name(name: &str) -> Option<Controller> {
+        if name.eq_ignore_ascii_case("human") {
+            return Some(Controller::Human);
+        }
+        return Difficulty::from_name(name).map(Controller::Ai);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Play,
+    Tui,
+    Analyze,
+    Arena,
+    Replay,
+    Serve,
+    Json,
+    Perft,
+    Book,
+    Help,
+}
+
+// everything given on the command line
+#[derive(Clone, Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub decks: Option<[Unit; 4]>,
+    // seats the AI plays without being asked
+    pub ai_plays: Vec<u8>,
+    pub difficulty: Option<Difficulty>,
+    pub depth: Option<(u8, u8)>,
+    pub time: Option<Duration>,
+    pub threads: Option<usize>,
+    pub quiescence: u8,
+    pub blue: Option<Controller>,
+    pub red: Option<Controller>,
+    pub delay: Option<Duration>,
+    pub games: usize,
+    pub seed: Option<u64>,
+    pub file: Option<String>,
+    pub html: Option<String>,
+    pub book: Option<String>,
+    // the opening book read from the book path by load_book
+    pub opening_book: Option<Arc<OpeningBook>>,
+    // arguments that are not options, their meaning depends on the command
+    pub rest: Vec<String>,
+}
+
+impl Default for Cli {
+    fn default() -> Cli {
+        return Cli {
+            command: Command::Play,
+            decks: None,
+            ai_plays: Vec::new(),
+            difficulty: None,
+            depth: None,
+            time: None,
+            threads: None,
+            quiescence: 0,
+            blue: None,
+            red: None,
+            delay: None,
+            games: 10,
+            seed: None,
+            file: None,
+            html: None,
+            book: None,
+            opening_book: None,
+            rest: Vec::new(),
+        };
+    }
+}
+
+impl Cli {
+    // parses the arguments given after the name of the program
+    pub fn parse(args: &[String]) -> Result<Cli, String> {
+        let mut cli = Cli::default();
+        let mut args = args.iter();
+
+        let mut command = None;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> Result<&String, String> {
+                return args.next().ok_or(format!("{} needs a value", name));
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => cli.command = Command::Help,
+                "--decks" => {
+                    let digits: Vec<&str> = (0..4)
+                        .map(|_| value(arg).map(|v| v.as_str()))
+                        .collect::<Result<Vec<&str>, String>>()?;
+                    cli.decks = Some(
+                        parse_decks(&digits.join(" "))
+                            .ok_or("--decks needs 4 unit types between 1 and 9")?,
+                    );
+                }
+                "--ai-plays" => {
+                    cli.ai_plays = match value(arg)?.to_lowercase().as_str() {
+                        "blue" | "1" => vec![1],
+                        "red" | "2" => vec![2],
+                        "both" => vec![1, 2],
+                        "none" => vec![],
+                        seat => return Err(format!("Unknown seat {}", seat)),
+                    }
+                }
+                "--difficulty" => {
+                    let name = value(arg)?;
+                    cli.difficulty = Some(
+                        Difficulty::from_name(name)
+                            .ok_or(format!("Unknown difficulty {}", name))?,
+                    );
+                }
+                "--blue" | "--red" => {
+                    let name = value(arg)?;
+                    let controller =
+                        Controller::from_name(name).ok_or(format!("Unknown player {}", name))?;
+                    if arg == "--blue" {
+                        cli.blue = Some(controller);
+                    } else {
+                        cli.red = Some(controller);
+                    }
+                }
+                "--depth" => cli.depth = Some(parse_depth(value(arg)?)?),
+                "--time" => cli.time = Some(parse_seconds(arg, value(arg)?)?),
+                "--delay" => cli.delay = Some(parse_seconds(arg, value(arg)?)?),
+                "--threads" => {
+                    cli.threads = Some(
+                        value(arg)?
+                            .parse()
+                            .ok()
+                            .filter(|&n: &usize| n > 0)
+                            .ok_or("--threads needs a positive number")?,
+                    )
+                }
+                "--quiescence" => {
+                    cli.quiescence = value(arg)?
+                        .parse()
+                        .map_err(|_| "--quiescence needs a number of plies")?
+                }
+                "--rules" => {
+                    let rules = value(arg)?;
+                    if rules != "standard" {
+                        return Err(format!(
+                            "Unknown rules {}, only standard is supported",
+                            rules
+                        ));
+                    }
+                }
+                "--games" => {
+                    cli.games = value(arg)?.parse().map_err(|_| "--games needs a number")?
+                }
+                "--seed" => {
+                    cli.seed = Some(value(arg)?.parse().map_err(|_| "--seed needs a number")?)
+                }
+                "--file" => cli.file = Some(value(arg)?.clone()),
+                "--html" => cli.html = Some(value(arg)?.clone()),
+                "--book" => cli.book = Some(value(arg)?.clone()),
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                _ if command.is_none() && cli.rest.is_empty() => {
+                    command = Some(match arg.as_str() {
+                        "play" => Command::Play,
+                        "tui" => Command::Tui,
+                        "analyze" | "analyse" => Command::Analyze,
+                        "arena" => Command::Arena,
+                        "replay" => Command::Replay,
+                        "serve" => Command::Serve,
+                        "json" => Command::Json,
+                        "perft" => Command::Perft,
+                        "book" => Command::Book,
+                        other => return Err(format!("Unknown command {}", other)),
+                    });
+                }
+                _ => cli.rest.push(arg.clone()),
+            }
+        }
+
+        if cli.command != Command::Help {
+            cli.command = command.unwrap_or(Command::Play);
+        }
+        return Ok(cli);
+    }
+
+    // the difficulty with the depth and time limit given on the command line
+    pub fn difficulty(&self) -> Difficulty {
+        return self.tune(self.difficulty.unwrap_or_default());
+    }
+
+    // a difficulty with the depth and time limit given on the command line
+    pub fn tune(&self, mut difficulty: Difficulty) -> Difficulty {
+        if self.depth.is_some() {
+            difficulty.depth = self.depth;
+        }
+        if self.time.is_some() {
+            difficulty.time = self.time;
+        }
+        return difficulty;
+    }
+
+    // who plays each seat: --blue and --red, then --ai-plays with --difficulty, humans otherwise.
+    // None if no seat was given
+    pub fn seats(&self) -> Option<[Controller; 2]> {
+        if self.blue.is_none() && self.red.is_none() && self.ai_plays.is_empty() {
+            return None;
+        }
+
+        let mut seats = [Controller::Human; 2];
+        for (i, given) in [self.blue, self.red].iter().enumerate() {
+            seats[i] = match *given {
+                Some(Controller::Ai(difficulty)) => Controller::Ai(self.tune(difficulty)),
+                Some(Controller::Human) => Controller::Human,
+                None if self.ai_plays.contains(&(i as u8 + 1)) => Controller::Ai(self.difficulty()),
+                None => Controller::Human,
+            };
+        }
+        return Some(seats);
+    }
+
+    // settings of the searches made for a difficulty
+    pub fn options(&self, difficulty: &Difficulty) -> SearchOptions {
+        // weaker levels pick among several searched moves instead of the single book move
+        let book = if difficulty.top <= 1 {
+            self.opening_book.clone()
+        } else {
+            None
+        };
+        return SearchOptions {
+            threads: self.threads,
+            quiescence: self.quiescence,
+            book,
+            ..difficulty.options()
+        };
+    }
+
+    // reads the opening book given with --book, if any
+    pub fn load_book(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.book {
+            self.opening_book = Some(Arc::new(OpeningBook::load(path)?));
+        }
+        return Ok(());
+    }
+
+    // the moves given as arguments or in the file, as (card, position)
+    pub fn moves(&self) -> Result<Vec<(usize, Position)>, String> {
+        let mut words: Vec<String> = self.rest.clone();
+        if let Some(path) = &self.file {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))?;
+            words.extend(text.split_whitespace().map(String::from));
+        }
+
+        return words
+            .iter()
+            .map(|word| parse_player_move(word).map_err(|e| format!("{}: {}", word, e)))
+            .collect();
+    }
+}
+
+// parses a positive number of seconds given to an option
+fn parse_seconds(name: &str, seconds: &str) -> Result<Duration, String> {
+    return seconds
+        .parse()
+        .ok()
+        .filter(|s: &f64| s.is_finite() && *s > 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or(format!("{} needs a positive number of seconds", name));
+}
+
+// parses D or D,P
+fn parse_depth(depth: &str) -> Result<(u8, u8), String> {
+    let numbers: Option<Vec<u8>> = depth.split(',').map(|n| n.trim().parse().ok()).collect();
+    return match numbers.as_deref() {
+        Some(&[d]) if d > 0 => Ok((d, d - 1)),
+        Some(&[d, p]) if d > 0 => Ok((d, p)),
+        _ => Err("--depth needs a depth like 4 or 4,2".to_string()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::DIFFICULTIES;
+
+    fn parse(line: &str) -> Result<Cli, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        return Cli::parse(&args);
+    }
+
+    #[test]
+    fn commands_and_options() {
+        let cli = parse("").unwrap();
+        assert_eq!(cli.command, Command::Play);
+        assert_eq!(cli.decks, None);
+
+        let cli = parse("play --decks 1 2 3 4 --ai-plays red --time 1.5 --threads 2").unwrap();
+        assert_eq!(cli.command, Command::Play);
+        assert_eq!(
+            cli.decks,
+            Some([Unit::Warden, Unit::Keeper, Unit::Siren, Unit::Saboteur])
+        );
+        assert_eq!(cli.ai_plays, vec![2]);
+        assert_eq!(cli.time, Some(Duration::from_millis(1500)));
+        assert_eq!(cli.threads, Some(2));
+
+        // standard is the only rule set so far
+        assert_eq!(
+            parse("--rules chaos").err(),
+            Some("Unknown rules chaos, only standard is supported".to_string())
+        );
+        let cli = parse("analyze 111 --depth 4 234 --rules standard").unwrap();
+        assert_eq!(cli.command, Command::Analyze);
+        assert_eq!(cli.depth, Some((4, 3)));
+        assert_eq!(cli.moves(), Ok(vec![(0, (0, 0)), (1, (2, 3))]));
+
+        let cli = parse("arena --blue easy --red 4 --games 3 --seed 9 --depth 3,1").unwrap();
+        assert_eq!(cli.command, Command::Arena);
+        assert_eq!(cli.blue, Some(Controller::Ai(DIFFICULTIES[1])));
+        assert_eq!(cli.red, Some(Controller::Ai(Difficulty::default())));
+        assert_eq!((cli.games, cli.seed, cli.depth), (3, Some(9), Some((3, 1))));
+
+        assert_eq!(
+            parse("serve 127.0.0.1:80").unwrap().rest,
+            vec!["127.0.0.1:80"]
+        );
+        assert_eq!(parse("perft 2 --help").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn command_line_overrides_the_difficulty() {
+        let cli = parse("--difficulty easy --time 3").unwrap();
+        let difficulty = cli.difficulty();
+        assert_eq!(difficulty.name, "Easy");
+        assert_eq!(difficulty.depth, Some((2, 1)));
+        assert_eq!(difficulty.time, Some(Duration::from_secs(3)));
+
+        let cli = parse("--depth 5,2 --threads 3").unwrap();
+        let options = cli.options(&cli.difficulty());
+        assert_eq!((options.depth, options.threads), (Some((5, 2)), Some(3)));
+        assert_eq!(options.quiescence, 0);
+        let cli = parse("--quiescence 2").unwrap();
+        assert_eq!(cli.options(&cli.difficulty()).quiescence, 2);
+    }
+
+    #[test]
+    fn seats_are_given_by_color_or_to_the_ai() {
+        assert_eq!(parse("--decks 1 2 3 4").unwrap().seats(), None);
+
+        let cli = parse("--ai-plays red --difficulty easy").unwrap();
+        assert_eq!(
+            cli.seats(),
+            Some([Controller::Human, Controller::Ai(DIFFICULTIES[1])])
+        );
+
+        // both seats played by different AIs, the depth applies to both
+        let cli = parse("--blue beginner --red hard --depth 3").unwrap();
+        let seats = cli.seats().unwrap();
+        assert_eq!(
+            seats[0],
+            Controller::Ai(Difficulty {
+                depth: Some((3, 2)),
+                ..DIFFICULTIES[0]
+            })
+        );
+        assert_eq!(
+            seats[1],
+            Controller::Ai(Difficulty {
+                depth: Some((3, 2)),
+                ..Difficulty::default()
+            })
+        );
+
+        let cli = parse("--ai-plays both --red human --delay 0.5").unwrap();
+        assert_eq!(
+            cli.seats(),
+            Some([Controller::Ai(Difficulty::default()), Controller::Human])
+        );
+        assert_eq!(cli.delay, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        for line in [
+            "--decks 1 2 3",
+            "--decks 1 2 3 0",
+            "--ai-plays green",
+            "--difficulty expert",
+            "--depth 0",
+            "--depth four",
+            "--time -1",
+            "--threads 0",
+            "--quiescence deep",
+            "--blue robot",
+            "--delay 0",
+            "--rules chaos",
+            "--games",
+            "--verbose",
+            "fly",
+        ] {
+            assert!(parse(line).is_err(), "{}", line);
+        }
+        assert!(parse("analyze 111 999").unwrap().moves().is_err());
+
+        // the book is only read when asked, so a bad path does not get in the way of --help
+        let mut cli = parse("--book /nonexistent/legions.book").unwrap();
+        assert!(cli.load_book().is_err());
+        let cli = parse("--help --book /nonexistent/legions.book").unwrap();
+        assert_eq!(cli.command, Command::Help);
+    }
+}
@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use crate::ai::{SearchOptions, SearchResult};
+use crate::rng::Rng;
+
+// how strongly the AI plays, weaker levels search less and pick among the best root moves
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Difficulty {
+    pub name: &'static str,
+    // fixed (maximum depth, pruning depth), picked by the number of cards left if None
+    pub depth: Option<(u8, u8)>,
+    // the search is stopped after this long, it runs to the end if None
+    pub time: Option<Duration>,
+    // each root score is moved by a random amount in -noise..=noise
+    pub noise: i8,
+    // the move is picked among this many of the best root moves
+    pub top: usize,
+    // softmax temperature in points of score, 0 always picks the best of them
+    pub temperature: f64,
+}
+
+pub const DIFFICULTIES: [Difficulty; 4] = [
+    Difficulty {
+        name: "Beginner",
+        depth: Some((1, 0)),
+        time: None,
+        noise: 3,
+        top: 5,
+        temperature: 3.0,
+    },
+    Difficulty {
+        name: "Easy",
+        depth: Some((2, 1)),
+        time: None,
+        noise: 1,
+        top: 3,
+        temperature: 1.5,
+    },
+    Difficulty {
+        name: "Medium",
+        depth: None,
+        time: Some(Duration::from_secs(2)),
+        noise: 0,
+        top: 2,
+        temperature: 0.5,
+    },
+    Difficulty {
+        name: "Hard",
+        depth: None,
+        time: None,
+        noise: 0,
+        top: 1,
+        temperature: 0.0,
+    },
+];
+
+impl Default for Difficulty {
+    // full strength
+    fn default() -> Difficulty {
+        return DIFFICULTIES[DIFFICULTIES.len() - 1];
+    }
+}
+
+impl Difficulty {
+    // a preset by name (any case) or by its number starting from 1
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        if let Ok(i) = name.parse::<usize>() {
+            return DIFFICULTIES.get(i.checked_sub(1)?).copied();
+        }
+        return DIFFICULTIES
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
+            .copied();
+    }
+
+    // settings of the search made at this difficulty
+    pub fn options(&self) -> SearchOptions {
+        return SearchOptions {
+            depth: self.depth,
+            time: self.time,
+            symmetry: true,
+            ..Default::default()
+        };
+    }
+
+    // picks the move to play among the root scores of a search as (card, row, column)
+    pub fn pick(&self, result: &SearchResult, player: u8, rng: &mut Rng) -> (usize, usize, usize) {
+        if result.scores.is_empty() {
+            let (card, y, x, _) = result.best;
+            return (card, y, x);
+        }
+
+        // scores from the point of view of the player with the noise added
+        let mut noisy: Vec<((usize, usize, usize), f64)> = result
+            .scores
+            .iter()
+            .map(|&(mov, score)| {
+                let score = if player == 1 { score } else { -score };
+                let noise = rng.below(2 * self.noise as usize + 1) as i32 - self.noise as i32;
+                (mov, (score as i32 + noise) as f64)
+            })
+            .collect();
+        // best first, equal scores by move so the pick only depends on the rng
+        noisy.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        noisy.truncate(self.top.max(1));
+
+        if self.temperature <= 0.0 {
+            return noisy[0].0;
+        }
+
+        // softmax over the kept moves
+        let best = noisy[0].1;
+        let weights: Vec<f64> = noisy
+            .iter()
+            .map(|&(_, score)| ((score - best) / self.temperature).exp())
+            .collect();
+        let mut r = rng.next_f64() * weights.iter().sum::<f64>();
+        for (i, weight) in weights.iter().enumerate() {
+            if r < *weight {
+                return noisy[i].0;
+            }
+            r -= weight;
+        }
+        return noisy[noisy.len() - 1].0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::SearchStats;
+
+    fn result(scores: Vec<((usize, usize, usize), i8)>) -> SearchResult {
+        return SearchResult {
+            best: (0, 0, 0, [None; 4]),
+            score: 0,
+            scores,
+            stats: SearchStats::default(),
+            elapsed: Duration::default(),
+            complete: true,
+        };
+    }
+
+    #[test]
+    fn presets_are_found_by_name_or_number() {
+        assert_eq!(Difficulty::from_name("easy"), Some(DIFFICULTIES[1]));
+        assert_eq!(Difficulty::from_name("4"), Some(Difficulty::default()));
+        assert_eq!(Difficulty::from_name("0"), None);
+        assert_eq!(Difficulty::from_name("5"), None);
+        assert_eq!(Difficulty::from_name("expert"), None);
+
+        // the time limit is enforced by the search itself
+        let medium = Difficulty::from_name("medium").unwrap();
+        assert_eq!(medium.options().time, Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn hard_plays_the_best_move() {
+        let result = result(vec![((0, 0, 0), 2), ((1, 2, 3), 5), ((2, 1, 1), -4)]);
+        let mut rng = Rng::new(1);
+        for _ in 0..50 {
+            assert_eq!(Difficulty::default().pick(&result, 1, &mut rng), (1, 2, 3));
+            assert_eq!(Difficulty::default().pick(&result, 2, &mut rng), (2, 1, 1));
+        }
+    }
+
+    #[test]
+    fn weaker_levels_stay_among_the_top_moves() {
+        let scores: Vec<((usize, usize, usize), i8)> =
+            (0..10).map(|i| ((i, 0, 0), i as i8 * 10)).collect();
+        let result = result(scores);
+        let mut rng = Rng::new(3);
+
+        for difficulty in DIFFICULTIES {
+            for _ in 0..200 {
+                let (card, _, _) = difficulty.pick(&result, 1, &mut rng);
+                // scores are 10 points apart, more than any noise
+                assert!(card >= 10 - difficulty.top, "{}", difficulty.name);
+            }
+        }
+
+        // a beginner does not always play the same move
+        let picks: Vec<(usize, usize, usize)> = (0..200)
+            .map(|_| DIFFICULTIES[0].pick(&result, 1, &mut rng))
+            .collect();
+        assert!(picks.iter().any(|&mov| mov != picks[0]));
+    }
+}
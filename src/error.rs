@@ -0,0 +1,67 @@
+use std::{error::Error, fmt};
+
+use crate::card::{Position, Unit};
+
+// reasons the rules engine refuses to place a card
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlaceError {
+    // there is no card at this index in the player's deck
+    CardIndexOutOfRange(usize),
+    // cell is not on the 4x5 board
+    CellOutOfBounds(Position),
+    // cell already holds a card
+    CellOccupied(Position, Unit),
+}
+
+// reasons a move entered by a player can not be played
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveError {
+    // input is not in the CardIndexYX format
+    BadFormat,
+    // the move was parsed but can not be placed
+    Place(PlaceError),
+}
+
+impl From<PlaceError> for MoveError {
+    fn from(e: PlaceError) -> MoveError {
+        MoveError::Place(e)
+    }
+}
+
+// positions and indexes are shown 1 based like they are entered
+impl fmt::Display for PlaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaceError::CardIndexOutOfRange(card) => {
+                write!(f, "There is no card #{} in the deck", card + 1)
+            }
+            PlaceError::CellOutOfBounds((y, x)) => {
+                write!(f, "{}-{} is not on the board", y + 1, x + 1)
+            }
+            PlaceError::CellOccupied((y, x), unit) => {
+                write!(
+                    f,
+                    "{}-{} is already occupied with a {:?}",
+                    y + 1,
+                    x + 1,
+                    unit
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::BadFormat => {
+                write!(f, "Move must be 3 digits as CardIndexYX (e.g. 111)")
+            }
+            MoveError::Place(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PlaceError {}
+
+impl Error for MoveError {}
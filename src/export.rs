@@ -0,0 +1,176 @@
+use std::fmt::Write;
+
+use crate::card::*;
+use crate::utility::*;
+
+// size of a cell and of the margin around the board in pixels
+const CELL: usize = 100;
+const MARGIN: usize = 10;
+
+const BLUE: &str = "#2f6fd6";
+const RED: &str = "#d63f2f";
+const EMPTY: &str = "#e8e4da";
+
+// renders a position (cards with their values and owners, bombs) as a standalone SVG image
+pub fn position_svg(board: &[[Option<Card>; 5]; 4], bombs: &[[u8; 5]; 4]) -> String {
+    let width = 5 * CELL + 2 * MARGIN;
+    let height = 4 * CELL + 2 * MARGIN;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\">\n",
+        w = width,
+        h = height
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" fill=\"#3b3a36\"/>",
+        width, height
+    );
+
+    for i in 0..4 {
+        for j in 0..5 {
+            let x = MARGIN + j * CELL;
+            let y = MARGIN + i * CELL;
+            let fill = match &board[i][j] {
+                Some(card) if card.player == 1 => BLUE,
+                Some(_) => RED,
+                None => EMPTY,
+            };
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"{}\" stroke=\"#3b3a36\" stroke-width=\"3\"/>",
+                x,
+                y,
+                CELL,
+                CELL,
+                fill
+            );
+
+            match &board[i][j] {
+                Some(card) => {
+                    // swarms are shown with the bonus of their allies
+                    let mut c = Card::copy(card);
+                    if c.name == Unit::Swarm {
+                        c.upgrade(Card::swarm_count(board, c.player));
+                    }
+                    let values = [
+                        (x + CELL / 2, y + 20, c.top),
+                        (x + CELL - 14, y + CELL / 2 + 6, c.right),
+                        (x + CELL / 2, y + CELL - 10, c.bottom),
+                        (x + 14, y + CELL / 2 + 6, c.left),
+                    ];
+                    for (vx, vy, value) in values {
+                        let _ = writeln!(
+                            svg,
+                            "<text x=\"{}\" y=\"{}\" font-size=\"18\" font-weight=\"bold\" fill=\"white\" text-anchor=\"middle\">{}</text>",
+                            vx, vy, value
+                        );
+                    }
+                    let _ = writeln!(
+                        svg,
+                        "<text x=\"{}\" y=\"{}\" font-size=\"13\" fill=\"white\" text-anchor=\"middle\">{:?}</text>",
+                        x + CELL / 2,
+                        y + CELL / 2 + 5,
+                        c.name
+                    );
+                }
+                None if bombs[i][j] > 0 => {
+                    let (cx, cy) = (x + CELL / 2, y + CELL / 2);
+                    let _ = writeln!(
+                        svg,
+                        "<circle cx=\"{}\" cy=\"{}\" r=\"16\" fill=\"#222\"/><text x=\"{}\" y=\"{}\" font-size=\"16\" font-weight=\"bold\" fill=\"#f5c542\" text-anchor=\"middle\">{}</text>",
+                        cx,
+                        cy,
+                        cx,
+                        cy + 6,
+                        bombs[i][j]
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+
+    svg += "</svg>\n";
+    return svg;
+}
+
+// renders every position of a game as a self-contained HTML page with a slider to go through the plies
+pub fn game_html(positions: &[([[Option<Card>; 5]; 4], [[u8; 5]; 4])]) -> String {
+    let last = positions.len().saturating_sub(1);
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Legions game</title>\n<style>\n\
+         body { font-family: sans-serif; background: #f4f1ea; text-align: center; }\n\
+         .ply { display: none; }\n\
+         .ply.shown { display: block; }\n\
+         input { width: 520px; }\n\
+         </style>\n</head>\n<body>\n<h1>Legions game</h1>\n",
+    );
+
+    for (ply, (board, bombs)) in positions.iter().enumerate() {
+        let (blue, red) = calc_scores(board);
+        let _ = writeln!(
+            html,
+            "<div class=\"ply{}\" id=\"ply{}\">\n<p>Ply {} of {}: Blue {} - {} Red</p>\n{}</div>",
+            if ply == last { " shown" } else { "" },
+            ply,
+            ply,
+            last,
+            blue,
+            red,
+            position_svg(board, bombs)
+        );
+    }
+
+    let _ = write!(
+        html,
+        "<p><input type=\"range\" id=\"slider\" min=\"0\" max=\"{last}\" value=\"{last}\"></p>\n\
+         <script>\n\
+         var slider = document.getElementById(\"slider\");\n\
+         slider.oninput = function () {{\n\
+         \x20 document.querySelector(\".ply.shown\").classList.remove(\"shown\");\n\
+         \x20 document.getElementById(\"ply\" + slider.value).classList.add(\"shown\");\n\
+         }};\n\
+         </script>\n</body>\n</html>\n",
+        last = last
+    );
+    return html;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    #[test]
+    fn svg_shows_cards_and_bombs() {
+        let mut game = GameState::new([Unit::Titan, Unit::Titan, Unit::Swarm, Unit::Swarm]);
+        game.apply_move(0, (0, 0)).unwrap();
+        game.apply_move(0, (3, 4)).unwrap();
+        game.bombs[2][2] = 3;
+        let svg = position_svg(&game.board, &game.bombs);
+
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect").count(), 1 + 20);
+        assert_eq!(svg.matches(BLUE).count(), 1);
+        assert_eq!(svg.matches(RED).count(), 1);
+        assert!(svg.contains(">Titan<") && svg.contains(">Swarm<"));
+        assert!(svg.contains("fill=\"#f5c542\" text-anchor=\"middle\">3<"));
+    }
+
+    #[test]
+    fn html_has_a_position_per_ply() {
+        let mut game = GameState::new([Unit::Titan, Unit::Warden, Unit::Swarm, Unit::Siren]);
+        for cell in [(0, 0), (1, 1), (2, 2)] {
+            game.apply_move(0, cell).unwrap();
+        }
+        let html = game_html(&game.positions());
+
+        assert_eq!(html.matches("<svg").count(), 4);
+        assert_eq!(html.matches("class=\"ply shown\"").count(), 1);
+        assert!(html.contains("id=\"ply3\""));
+        assert!(html.contains("max=\"3\" value=\"3\""));
+        // nothing is fetched from the network
+        assert!(!html.contains("src="));
+    }
+}
@@ -0,0 +1,634 @@
+use std::{
+    cmp::Reverse,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::ai::{
+    ai, default_depth, perft, perft_divide, same_card, SearchHandle, SearchOptions, SearchResult,
+};
+use crate::card::*;
+use crate::error::PlaceError;
+use crate::tt::TranspositionTable;
+use crate::utility::*;
+
+// a single match: board, bombs, both decks and the played moves
+pub struct GameState {
+    pub board: [[Option<Card>; 5]; 4],
+    pub bombs: [[u8; 5]; 4],
+    pub deck1: Vec<Card>,
+    pub deck2: Vec<Card>,
+    pub turn: u8,
+    // previous states for undo (board, bombs, card_ind, card)
+    history: Vec<([[Option<Card>; 5]; 4], [[u8; 5]; 4], usize, Card)>,
+    // searched positions kept between searches, allocated by the first search
+    tt: Option<Arc<TranspositionTable>>,
+    // background search running while the opponent thinks
+    ponder: Option<Ponder>,
+    // searches completed by the last ponder
+    pondered: Vec<(PonderKey, SearchResult)>,
+}
+
+// the recommended move of a position and why it is good
+pub struct Hint {
+    // move as (card, row, column) and the unit played
+    pub mov: (usize, usize, usize),
+    pub unit: Unit,
+    // minimax score of the move and static evaluation of the position before it
+    pub score: i8,
+    pub before: i8,
+    // what happens right away when the move is played, with its events
+    pub outcome: MoveOutcome,
+    // the opponent's best reply as ((card, row, column), unit, score), None if the game ends
+    pub reply: Option<((usize, usize, usize), Unit, i8)>,
+}
+
+impl Hint {
+    // expected change of the card difference for the player, without the bonus of winning
+    pub fn swing(&self, player: u8) -> i8 {
+        let score = match self.score {
+            s if s > 50 => s - 100,
+            s if s < -50 => s + 100,
+            s => s,
+        };
+        return if player == 1 {
+            score - self.before
+        } else {
+            self.before - score
+        };
+    }
+}
+
+// a move scoring worse than the best move of the position
+pub struct Blunder {
+    // minimax score of the move
+    pub score: i8,
+    // best move as (card, row, column) and its score
+    pub best: (usize, usize, usize),
+    pub best_score: i8,
+}
+
+impl Blunder {
+    // points lost by the player compared to the best move
+    pub fn loss(&self, player: u8) -> u8 {
+        let loss = if player == 1 {
+            self.best_score as i16 - self.score as i16
+        } else {
+            self.score as i16 - self.best_score as i16
+        };
+        return loss.max(0) as u8;
+    }
+
+    // whether the best move wins and the move does not
+    pub fn gives_up_a_win(&self, player: u8) -> bool {
+        return if player == 1 {
+            self.best_score > 50 && self.score <= 50
+        } else {
+            self.best_score < -50 && self.score >= -50
+        };
+    }
+}
+
+// position and settings a search was made for:
+// (hash, depth, quiescence, symmetry, seed, time, with a book)
+type PonderKey = (u64, (u8, u8), u8, bool, Option<u64>, Option<Duration>, bool);
+
+struct Ponder {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Vec<(PonderKey, SearchResult)>>,
+}
+
+fn ponder_key(
+    board: &[[Option<Card>; 5]; 4],
+    bombs: &[[u8; 5]; 4],
+    deck1: &[Card],
+    deck2: &[Card],
+    player: u8,
+    options: &SearchOptions,
+) -> PonderKey {
+    let depth = options
+        .depth
+        .unwrap_or_else(|| default_depth(deck1.len() + deck2.len()));
+    return (
+        TranspositionTable::hash(board, bombs, deck1, deck2, player),
+        depth,
+        options.quiescence,
+        options.symmetry,
+        options.seed,
+        options.time,
+        options.book.is_some(),
+    );
+}
+
+impl GameState {
+    // starts a game with two unit types per player (player1 player1 player2 player2)
+    pub fn new(units: [Unit; 4]) -> GameState {
+        // each player can only hold 8 cards
+        let mut deck1: Vec<Card> = Vec::with_capacity(8);
+        let mut deck2: Vec<Card> = Vec::with_capacity(8);
+
+        Card::add_to_deck(&mut deck1, units[0], 1);
+        Card::add_to_deck(&mut deck1, units[1], 1);
+        Card::add_to_deck(&mut deck2, units[2], 2);
+        Card::add_to_deck(&mut deck2, units[3], 2);
+
+        return GameState {
+            board: Default::default(),
+            bombs: [[0; 5]; 4],
+            deck1,
+            deck2,
+            turn: 0,
+            history: Vec::new(),
+            tt: None,
+            ponder: None,
+            pondered: Vec::new(),
+        };
+    }
+
+    // player whose turn it is
+    pub fn current_player(&self) -> u8 {
+        return (self.turn % 2) + 1;
+    }
+
+    // deck of the specified player
+    pub fn deck(&self, player: u8) -> &Vec<Card> {
+        if player == 1 {
+            &self.deck1
+        } else {
+            &self.deck2
+        }
+    }
+
+    // the game ends when there are no more cards
+    pub fn is_over(&self) -> bool {
+        return self.deck1.len() + self.deck2.len() == 0;
+    }
+
+    pub fn scores(&self) -> (i8, i8) {
+        return calc_scores(&self.board);
+    }
+
+    // every position of the game as (board, bombs), from the start to the current one
+    pub fn positions(&self) -> Vec<([[Option<Card>; 5]; 4], [[u8; 5]; 4])> {
+        let mut positions: Vec<([[Option<Card>; 5]; 4], [[u8; 5]; 4])> = self
+            .history
+            .iter()
+            .map(|(board, bombs, _, _)| (copy_board(board), *bombs))
+            .collect();
+        positions.push((copy_board(&self.board), self.bombs));
+        return positions;
+    }
+
+    // plays a card of the current player on the board, returning what happened with its events
+    pub fn apply_move(
+        &mut self,
+        card: usize,
+        position: Position,
+    ) -> Result<MoveOutcome, PlaceError> {
+        let player = self.current_player();
+        Card::check_move(&self.board, self.deck(player), card, position)?;
+
+        let played = Card::copy(&self.deck(player)[card]);
+
+        // fetch neighbours of this move
+        let mut neighbours = Card::get_neighbours(&self.board, position.0, position.1, played.name);
+
+        let prev_board = copy_board(&self.board);
+        let prev_bombs = self.bombs;
+
+        let outcome = Card::place_card_narrated(
+            &mut self.board,
+            &mut self.deck1,
+            &mut self.deck2,
+            card,
+            position,
+            player,
+            &mut self.bombs,
+            &mut neighbours,
+        )?;
+
+        self.history.push((prev_board, prev_bombs, card, played));
+        self.turn += 1;
+
+        return Ok(outcome);
+    }
+
+    // reverts the last played move. returns whether there was a move to undo
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some((board, bombs, card_ind, card)) => {
+                self.board = board;
+                self.bombs = bombs;
+                self.turn -= 1;
+                // put the card back into the deck it came from
+                if self.current_player() == 1 {
+                    self.deck1.insert(card_ind, card);
+                } else {
+                    self.deck2.insert(card_ind, card);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // searches for the best move of the current player without playing it
+    pub fn best_move(&mut self, options: &SearchOptions) -> SearchResult {
+        return self.search(options).wait();
+    }
+
+    // searches the best move of the current player and the opponent's best reply to it
+    pub fn hint(&mut self, options: &SearchOptions) -> Hint {
+        let player = self.current_player();
+        let before = evaluation(&self.board);
+
+        let result = self.best_move(options);
+        let (card, y, x, _) = result.best;
+        let unit = self.deck(player)[card].name;
+        let outcome = self
+            .apply_move(card, (y, x))
+            .expect("Best move is illegal !");
+
+        let reply = if self.is_over() {
+            None
+        } else {
+            let result = self.best_move(options);
+            let (card, y, x, _) = result.best;
+            Some((
+                (card, y, x),
+                self.deck(player % 2 + 1)[card].name,
+                result.score,
+            ))
+        };
+        assert!(self.undo(), "Hinted move could not be undone !");
+
+        return Hint {
+            mov: (card, y, x),
+            unit,
+            score: result.score,
+            before,
+            outcome,
+            reply,
+        };
+    }
+
+    // compares a move of the current player to the best move, None if it is as good
+    pub fn check_blunder(
+        &mut self,
+        card: usize,
+        position: Position,
+        options: &SearchOptions,
+    ) -> Result<Option<Blunder>, PlaceError> {
+        let player = self.current_player();
+        Card::check_move(&self.board, self.deck(player), card, position)?;
+
+        // identical cards are only searched once, under the first of them
+        let deck = self.deck(player);
+        let card = deck
+            .iter()
+            .position(|c| same_card(c, &deck[card]))
+            .unwrap_or(card);
+
+        // every move needs a score so the book is left out
+        let options = SearchOptions {
+            book: None,
+            ..options.clone()
+        };
+        let result = self.best_move(&options);
+        let score = match result
+            .scores
+            .iter()
+            .find(|&&(mov, _)| mov == (card, position.0, position.1))
+        {
+            Some(&(_, score)) => score,
+            None => self.move_score(card, position, &options),
+        };
+        let as_good = if player == 1 {
+            score >= result.score
+        } else {
+            score <= result.score
+        };
+        if as_good {
+            return Ok(None);
+        }
+
+        let (best, y, x, _) = result.best;
+        return Ok(Some(Blunder {
+            score,
+            best: (best, y, x),
+            best_score: result.score,
+        }));
+    }
+
+    // searches a single legal move to the depth the root search gives every move, for a move the
+    // time limit cut off or that was skipped as the mirror of another one
+    fn move_score(&mut self, card: usize, position: Position, options: &SearchOptions) -> i8 {
+        let depth = options
+            .depth
+            .unwrap_or_else(|| default_depth(self.deck1.len() + self.deck2.len()));
+        let unlimited = SearchOptions {
+            time: None,
+            ..options.clone()
+        };
+
+        // the moves after it are searched one ply shallower, which is the move's own score
+        self.apply_move(card, position)
+            .expect("Checked move is illegal !");
+        let score = if depth.0 > 1 && !self.is_over() {
+            Some(
+                self.best_move(&SearchOptions {
+                    depth: Some((depth.0 - 1, depth.1)),
+                    ..unlimited.clone()
+                })
+                .score,
+            )
+        } else {
+            None
+        };
+        assert!(self.undo(), "Checked move could not be undone !");
+
+        // too shallow to split, every move is searched again, mirrored ones included
+        return score.unwrap_or_else(|| {
+            self.best_move(&SearchOptions {
+                symmetry: false,
+                ..unlimited
+            })
+            .scores
+            .iter()
+            .find(|&&(mov, _)| mov == (card, position.0, position.1))
+            .map(|&(_, score)| score)
+            .expect("Checked move was not searched !")
+        });
+    }
+
+    // starts searching for the best move of the current player in the background, pondering is
+    // stopped and its result is used if it already searched this position
+    pub fn search(&mut self, options: &SearchOptions) -> SearchHandle {
+        let player = self.current_player();
+
+        self.stop_pondering();
+        let key = ponder_key(
+            &self.board,
+            &self.bombs,
+            &self.deck1,
+            &self.deck2,
+            player,
+            options,
+        );
+        if let Some(i) = self.pondered.iter().position(|(k, _)| *k == key) {
+            return SearchHandle::finished(self.pondered.swap_remove(i).1);
+        }
+
+        return SearchHandle::start(
+            &self.board,
+            &self.deck1,
+            &self.deck2,
+            player,
+            &self.bombs,
+            options,
+            self.tt.get_or_insert_with(Default::default),
+        );
+    }
+
+    // keeps searching in the background while the opponent thinks: their replies are ranked by a
+    // shallow search, then our best move after each of them is searched in that order until the
+    // next call to best_move. the transposition table is shared with the later searches
+    pub fn ponder(&mut self, options: &SearchOptions) {
+        self.stop_pondering();
+        if self.is_over() {
+            return;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let options = SearchOptions {
+            progress: None,
+            stop: Some(Arc::clone(&stop)),
+            ..options.clone()
+        };
+        let tt = Arc::clone(self.tt.get_or_insert_with(Default::default));
+        let stopped = Arc::clone(&stop);
+
+        // the opponent is the one to move
+        let opponent = self.current_player();
+        let mut board = copy_board(&self.board);
+        let mut bombs = self.bombs;
+        let deck1: Vec<Card> = self.deck1.iter().map(Card::copy).collect();
+        let deck2: Vec<Card> = self.deck2.iter().map(Card::copy).collect();
+
+        let thread = thread::spawn(move || {
+            let mut results = Vec::new();
+
+            // every reply needs a score, so neither the book nor the time limit cuts it short
+            let ranking = SearchOptions {
+                depth: Some((1, 0)),
+                book: None,
+                time: None,
+                ..options.clone()
+            };
+            let mut replies = ai(
+                &mut board, &deck1, &deck2, opponent, &mut bombs, &ranking, &tt,
+            )
+            .scores;
+            if opponent == 1 {
+                replies.sort_by_key(|&(_, score)| Reverse(score));
+            } else {
+                replies.sort_by_key(|&(_, score)| score);
+            }
+
+            for ((card, y, x), _) in replies {
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                // play the reply on copies
+                let mut t_board = copy_board(&board);
+                let mut t_bombs = bombs;
+                let mut t_deck1: Vec<Card> = deck1.iter().map(Card::copy).collect();
+                let mut t_deck2: Vec<Card> = deck2.iter().map(Card::copy).collect();
+                let unit = if opponent == 1 {
+                    deck1[card].name
+                } else {
+                    deck2[card].name
+                };
+                let mut neighbours = Card::get_neighbours(&t_board, y, x, unit);
+                Card::place_card(
+                    &mut t_board,
+                    &mut t_deck1,
+                    &mut t_deck2,
+                    card,
+                    (y, x),
+                    opponent,
+                    &mut t_bombs,
+                    &mut neighbours,
+                )
+                .expect("Generated move is illegal !");
+
+                // the game ended with this reply
+                if t_deck2.is_empty() {
+                    continue;
+                }
+
+                let player = (opponent % 2) + 1;
+                let key = ponder_key(&t_board, &t_bombs, &t_deck1, &t_deck2, player, &options);
+                let result = ai(
+                    &mut t_board,
+                    &t_deck1,
+                    &t_deck2,
+                    player,
+                    &mut t_bombs,
+                    &options,
+                    &tt,
+                );
+
+                // a search cut short is meaningless
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                results.push((key, result));
+            }
+
+            return results;
+        });
+
+        self.ponder = Some(Ponder { stop, thread });
+    }
+
+    // whether pondering is still searching
+    pub fn is_pondering(&self) -> bool {
+        return match &self.ponder {
+            Some(ponder) => !ponder.thread.is_finished(),
+            None => false,
+        };
+    }
+
+    // stops pondering, keeping the searches it completed
+    fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.stop.store(true, Ordering::Relaxed);
+            self.pondered = ponder.thread.join().expect("Pondering thread panicked !");
+        }
+    }
+
+    // number of positions reached after depth moves, 1 at depth 0 or once the game is over
+    pub fn perft_total(&mut self, depth: u8) -> u64 {
+        let player = self.current_player();
+        return perft(
+            &mut self.board,
+            &mut self.deck1,
+            &mut self.deck2,
+            &mut self.bombs,
+            player,
+            depth,
+        );
+    }
+
+    // number of positions reached after depth moves, broken down by the first move of the current
+    // player. empty at depth 0 or once the game is over
+    pub fn perft(&mut self, depth: u8) -> Vec<((usize, usize, usize), u64)> {
+        let player = self.current_player();
+        return perft_divide(
+            &mut self.board,
+            &mut self.deck1,
+            &mut self.deck2,
+            &mut self.bombs,
+            player,
+            depth,
+        );
+    }
+}
+
+impl Drop for GameState {
+    // a ponder left running would search for nothing, it is stopped and waited for so it never
+    // outlives the game. a panic of the thread is not raised again while dropping
+    fn drop(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.stop.store(true, Ordering::Relaxed);
+            let _ = ponder.thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::OpeningBook;
+
+    #[test]
+    fn blunder_loss_is_never_negative() {
+        let blunder = Blunder {
+            score: -3,
+            best: (0, 0, 0),
+            best_score: 5,
+        };
+        assert_eq!(blunder.loss(1), 8);
+        // a move scoring better for the player than the best one loses nothing
+        assert_eq!(blunder.loss(2), 0);
+
+        let blunder = Blunder {
+            score: 127,
+            best: (0, 0, 0),
+            best_score: -128,
+        };
+        assert_eq!(blunder.loss(2), 255);
+    }
+
+    #[test]
+    fn single_moves_score_like_the_root_search() {
+        let mut game = GameState::new([Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Saboteur]);
+        while game.deck1.len() + game.deck2.len() > 8 {
+            let (card, y, x) = game.perft(1)[0].0;
+            game.apply_move(card, (y, x)).unwrap();
+        }
+
+        for depth in [(1, 0), (2, 1), (3, 2)] {
+            let options = SearchOptions {
+                depth: Some(depth),
+                ..Default::default()
+            };
+            let result = game.best_move(&options);
+            for ((card, y, x), score) in result.scores {
+                assert_eq!(
+                    game.move_score(card, (y, x), &options),
+                    score,
+                    "{:?}",
+                    depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn book_does_not_narrow_the_replies_pondered() {
+        let mut game = GameState::new([Unit::Siren, Unit::Titan, Unit::Saboteur, Unit::Swarm]);
+        while game.deck1.len() + game.deck2.len() > 4 {
+            let (card, y, x) = game.perft(1)[0].0;
+            game.apply_move(card, (y, x)).unwrap();
+        }
+
+        // the book knows the opponent's move in this position
+        let key = TranspositionTable::hash(
+            &game.board,
+            &game.bombs,
+            &game.deck1,
+            &game.deck2,
+            game.current_player(),
+        );
+        let mut book = OpeningBook::default();
+        book.insert(key, game.perft(1)[0].0, 42);
+
+        game.ponder(&SearchOptions {
+            depth: Some((2, 1)),
+            book: Some(Arc::new(book)),
+            ..Default::default()
+        });
+        while game.is_pondering() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        game.stop_pondering();
+        assert!(game.pondered.len() > 1);
+    }
+}
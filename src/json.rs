@@ -0,0 +1,397 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+// arrays and objects nested deeper than this are refused so the parser can't run out of stack
+const MAX_DEPTH: usize = 64;
+
+// a minimal JSON value used by the structured protocol
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    // builds an object out of key value pairs keeping their order
+    pub fn object(pairs: Vec<(&str, Value)>) -> Value {
+        return Value::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    }
+
+    // fetches a field of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    // returns the number if it is a non negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    // parses a JSON document
+    pub fn parse(text: &str) -> Result<Value, String> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+
+        skip_whitespace(&mut chars);
+        if let Some(c) = chars.next() {
+            return Err(format!("Unexpected trailing character '{}'", c));
+        }
+
+        return Ok(value);
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(n: u8) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<i8> for Value {
+    fn from(n: i8) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Value {
+        Value::Array(v.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+// writes a string with the required escapes
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// serializes the value on a single line
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => {
+                if n.fract() == 0.0 && n.abs() < 1e15 {
+                    write!(f, "{}", *n as i64)
+                } else {
+                    write!(f, "{}", n)
+                }
+            }
+            Value::String(s) => write_string(f, s),
+            Value::Array(a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(pairs) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while let Some(c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else {
+            break;
+        }
+    }
+}
+
+// consumes the expected literal (true, false, null)
+fn expect_literal(chars: &mut Peekable<Chars>, literal: &str) -> Result<(), String> {
+    for expected in literal.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("Invalid literal, expected '{}'", literal));
+        }
+    }
+    return Ok(());
+}
+
+// parses the value starting at the next character, `depth` being the number of arrays and
+// objects it is nested in
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        None => Err("Unexpected end of input".to_string()),
+        Some('n') => expect_literal(chars, "null").map(|_| Value::Null),
+        Some('t') => expect_literal(chars, "true").map(|_| Value::Bool(true)),
+        Some('f') => expect_literal(chars, "false").map(|_| Value::Bool(false)),
+        Some('"') => parse_string(chars).map(Value::String),
+        Some('[') | Some('{') if depth >= MAX_DEPTH => {
+            Err(format!("Nested deeper than {} levels", MAX_DEPTH))
+        }
+        Some('[') => parse_array(chars, depth),
+        Some('{') => parse_object(chars, depth),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => Err(format!("Unexpected character '{}'", c)),
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    let mut number = String::new();
+    while let Some(c) = chars.peek() {
+        if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+            number.push(*c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+
+    return number
+        .parse::<f64>()
+        .map(Value::Number)
+        .map_err(|_| format!("Invalid number '{}'", number));
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    // skip the opening quote
+    chars.next();
+
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("Unterminated string".to_string()),
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('"') => s.push('"'),
+                Some('\\') => s.push('\\'),
+                Some('/') => s.push('/'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('n') => s.push('\n'),
+                Some('r') => s.push('\r'),
+                Some('t') => s.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16)
+                        .map_err(|_| format!("Invalid unicode escape '{}'", hex))?;
+                    s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                _ => return Err("Invalid escape sequence".to_string()),
+            },
+            Some(c) => s.push(c),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    // skip the opening bracket
+    chars.next();
+
+    let mut array = Vec::new();
+    skip_whitespace(chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+        return Ok(Value::Array(array));
+    }
+
+    loop {
+        array.push(parse_value(chars, depth + 1)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(']') => return Ok(Value::Array(array)),
+            _ => return Err("Expected ',' or ']' in array".to_string()),
+        }
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    // skip the opening brace
+    chars.next();
+
+    let mut pairs = Vec::new();
+    skip_whitespace(chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(Value::Object(pairs));
+    }
+
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() != Some(&'"') {
+            return Err("Expected a string key in object".to_string());
+        }
+        let key = parse_string(chars)?;
+
+        skip_whitespace(chars);
+        if chars.next() != Some(':') {
+            return Err("Expected ':' after object key".to_string());
+        }
+
+        let value = parse_value(chars, depth + 1)?;
+        pairs.push((key, value));
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => return Ok(Value::Object(pairs)),
+            _ => return Err("Expected ',' or '}' in object".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip() {
+        let text = "{\"a\":[1,-2.5,true,null],\"b\":{\"c\":\"d\\\"\\n\"},\"e\":[]}";
+        let value = Value::parse(text).unwrap();
+        assert_eq!(value.get("a").and_then(|a| a.as_array()).unwrap().len(), 4);
+        assert_eq!(
+            value.get("b").and_then(|b| b.get("c")),
+            Some(&Value::from("d\"\n"))
+        );
+        assert_eq!(value.to_string(), text);
+        assert_eq!(Value::parse(" \t[ 1 , 2 ]\n").unwrap(), vec![1u8, 2].into());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |levels: usize| format!("{}{}", "[".repeat(levels), "]".repeat(levels));
+        assert!(Value::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Value::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Value::parse(&"{\"a\":".repeat(MAX_DEPTH + 1)).is_err());
+
+        // far too deep for the stack if it was parsed
+        assert!(Value::parse(&"[".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn unicode_escapes_are_decoded() {
+        assert_eq!(
+            Value::parse("\"\\u0041\\u00e9\\u20AC\"").unwrap(),
+            Value::from("Aé€")
+        );
+        // a lone surrogate can't be a char
+        assert_eq!(
+            Value::parse("\"\\ud83d\"").unwrap(),
+            Value::from("\u{fffd}")
+        );
+        assert!(Value::parse("\"\\u00zz\"").is_err());
+        assert!(Value::parse("\"\\u12\"").is_err());
+        assert!(Value::parse("\"\\x\"").is_err());
+        assert!(Value::parse("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn numbers_are_checked() {
+        assert_eq!(Value::parse("-1.5e2").unwrap(), Value::Number(-150.0));
+        assert_eq!(Value::parse("7").unwrap().as_u64(), Some(7));
+        assert_eq!(Value::parse("-7").unwrap().as_u64(), None);
+        assert_eq!(Value::parse("7.5").unwrap().as_u64(), None);
+        for number in ["1-2", "--1", "1e", "1.2.3", "-"] {
+            assert!(Value::parse(number).is_err(), "{}", number);
+        }
+    }
+
+    #[test]
+    fn malformed_documents_are_refused() {
+        for text in [
+            "",
+            "1 2",
+            "{} x",
+            "[1,]",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{a:1}",
+            "{\"a\":1,}",
+            "tru",
+            "nul",
+        ] {
+            assert!(Value::parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+pub mod ai;
+pub mod book;
+pub mod card;
+pub mod cli;
+pub mod difficulty;
+pub mod error;
+pub mod export;
+pub mod game;
+pub mod json;
+pub mod protocol;
+pub mod rng;
+pub mod server;
+pub mod tt;
+pub mod tui;
+pub mod utility;
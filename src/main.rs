@@ -1,21 +1,112 @@
 use std::{
+    env, fs,
     io::{stdin, stdout, Write},
-    panic,
+    net::TcpListener,
+    process, thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-mod ai;
-mod card;
-mod utility;
-
-use ai::ai;
-use card::*;
-use utility::*;
+use legions_ai::{
+    ai::SearchOptions,
+    book::{self, OpeningBook},
+    card::*,
+    cli::{Cli, Command, Controller, USAGE},
+    difficulty::{Difficulty, DIFFICULTIES},
+    export, flush,
+    game::GameState,
+    input, protocol,
+    rng::Rng,
+    server, tui,
+    utility::*,
+};
 
 fn main() {
-    // each player can only hold 8 cards
-    let mut deck1: Vec<Card> = Vec::with_capacity(8);
-    let mut deck2: Vec<Card> = Vec::with_capacity(8);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut cli = match Cli::parse(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{} !\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if cli.command != Command::Help {
+        cli.load_book().unwrap_or_else(|e| fail(&e));
+    }
 
+    match cli.command {
+        Command::Help => println!("{}", USAGE),
+        // structured JSON-lines mode for integrations
+        Command::Json => protocol::run(stdin().lock(), stdout()),
+        // HTTP server on the LAN
+        Command::Serve => {
+            let address = cli.rest.first().map_or("0.0.0.0:8080", |a| a.as_str());
+            let listener = TcpListener::bind(address).expect("Could not bind the server address !");
+            println!("Serving on http://{}", address);
+            server::serve(listener);
+        }
+        // move generation counter from the start of a game: perft depth [p1 p1 p2 p2]
+        Command::Perft => {
+            let depth: u8 = cli
+                .rest
+                .first()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or_else(|| fail("Enter the depth as a number"));
+            let units = decks(&cli, &cli.rest[1.min(cli.rest.len())..]);
+
+            let mut game = GameState::new(units);
+            for ((card, y, x), count) in game.perft(depth) {
+                println!("{}{}{}: {}", card + 1, y + 1, x + 1, count);
+            }
+            println!("\nTotal: {}", game.perft_total(depth));
+        }
+        // full screen game with the keyboard
+        Command::Tui => {
+            let mut game = GameState::new(decks(&cli, &cli.rest));
+            let difficulty = cli.difficulty();
+            tui::run(
+                &mut game,
+                &difficulty,
+                &cli.options(&difficulty),
+                &mut rng(&cli),
+            )
+            .expect("Could not use the terminal !");
+        }
+        Command::Analyze => analyze(&cli),
+        Command::Arena => arena(&cli),
+        Command::Replay => replay(&cli),
+        Command::Book => book(&cli),
+        Command::Play => play(&cli),
+    }
+}
+
+// prints the error and the usage, then exits
+fn fail(msg: &str) -> ! {
+    eprintln!("{} !\n\n{}", msg, USAGE);
+    process::exit(2);
+}
+
+// unit types from --decks, the given arguments or the prompt, in that order
+fn decks(cli: &Cli, args: &[String]) -> [Unit; 4] {
+    if let Some(units) = cli.decks {
+        return units;
+    }
+    if !args.is_empty() {
+        return parse_decks(&args.join(" "))
+            .unwrap_or_else(|| fail("Enter exactly 4 unit types between 1 and 9"));
+    }
+    return prompt_decks();
+}
+
+// the game after the moves given on the command line, which needs --decks
+fn replayed(cli: &Cli) -> (GameState, Vec<(usize, Position)>) {
+    let units = cli
+        .decks
+        .unwrap_or_else(|| fail("Enter the unit types with --decks"));
+    let moves = cli.moves().unwrap_or_else(|e| fail(&e));
+    return (GameState::new(units), moves);
+}
+
+fn prompt_decks() -> [Unit; 4] {
     // prompt to input deck types
     print!(
         "
@@ -27,60 +118,358 @@ fn main() {
     6\tTitan
     7\tSlayer
     8\tSwarm
-    9\tLancer\n
-    Enter players deck unit types (player1 player1 player2 player2): "
+    9\tLancer\n"
+    );
+
+    // map input to determine card unit types, prompting again until it is valid
+    return loop {
+        print!("    Enter players deck unit types (player1 player1 player2 player2): ");
+        flush!();
+
+        // take input
+        let mut deck_types = String::new();
+        input!(
+            deck_types,
+            "You did not enter the numbers in correct format!"
+        );
+
+        match parse_decks(&deck_types) {
+            Some(units) => break units,
+            None => println!("    Enter exactly 4 unit types between 1 and 9 !"),
+        }
+    };
+}
+
+// asks who plays a seat
+fn prompt_seat(name: &str) -> Controller {
+    return loop {
+        print!(
+            "    Who plays {} ? (empty for a human, or an AI difficulty from 1 to {}): ",
+            name,
+            DIFFICULTIES.len()
+        );
+        flush!();
+
+        let mut player = String::new();
+        input!(player, "You did not enter the player in correct format!");
+        if player.trim().is_empty() {
+            break Controller::Human;
+        }
+        match Controller::from_name(player.trim()) {
+            Some(controller) => break controller,
+            None => println!(
+                "    Enter human or a difficulty between 1 and {} !",
+                DIFFICULTIES.len()
+            ),
+        }
+    };
+}
+
+fn prompt_difficulty() -> Difficulty {
+    // prompt for how strongly the AI plays
+    println!();
+    for (i, difficulty) in DIFFICULTIES.iter().enumerate() {
+        println!("    {}\t{}", i + 1, difficulty.name);
+    }
+    return loop {
+        print!(
+            "    Enter the AI difficulty (empty for {}): ",
+            Difficulty::default().name
+        );
+        flush!();
+
+        let mut level = String::new();
+        input!(level, "You did not enter the difficulty in correct format!");
+        if level.trim().is_empty() {
+            break Difficulty::default();
+        }
+        match level.trim().parse::<usize>() {
+            Ok(i) if (1..=DIFFICULTIES.len()).contains(&i) => break DIFFICULTIES[i - 1],
+            _ => println!(
+                "    Enter a difficulty between 1 and {} !",
+                DIFFICULTIES.len()
+            ),
+        }
+    };
+}
+
+// seeded from the command line or from the clock
+fn rng(cli: &Cli) -> Rng {
+    return Rng::new(cli.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| t.as_nanos() as u64)
+    }));
+}
+
+// searches the current position and picks the move to play at the difficulty, showing the
+// progress of the search if asked
+fn think(
+    game: &mut GameState,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+    rng: &mut Rng,
+    show_progress: bool,
+) -> (usize, Position) {
+    let player = game.current_player();
+    let search = game.search(options);
+
+    // show the score each time a better move is found
+    if show_progress {
+        print!("Progress: ");
+        flush!();
+    }
+    for progress in search.progress().iter() {
+        if !show_progress {
+            continue;
+        }
+        if progress.last == progress.best {
+            print!("({})", progress.best.1);
+        } else {
+            print!("|");
+        }
+        flush!();
+    }
+    if show_progress {
+        println!();
+    }
+
+    let result = search.wait();
+    let (card_ind, y, x) = difficulty.pick(&result, player, rng);
+    let score = result
+        .scores
+        .iter()
+        .find(|&&(mov, _)| mov == (card_ind, y, x))
+        .map_or(result.score, |&(_, score)| score);
+    if show_progress && ((player == 1 && score > 100) || (player == 2 && score < -100)) {
+        println!("\n  Omae wa mou shindeiru\n");
+    }
+    return (card_ind, (y, x));
+}
+
+// scores every move of the position reached after the given moves
+fn analyze(cli: &Cli) {
+    let (mut game, moves) = replayed(cli);
+    for (card, position) in moves {
+        if let Err(e) = game.apply_move(card, position) {
+            fail(&e.to_string());
+        }
+    }
+    if game.is_over() {
+        fail("The game is over");
+    }
+
+    // every move is scored so the book is left out
+    let difficulty = cli.difficulty();
+    let options = SearchOptions {
+        book: None,
+        ..cli.options(&difficulty)
+    };
+    let result = game.best_move(&options);
+
+    // best moves of the current player first
+    let player = game.current_player();
+    let mut scores = result.scores.clone();
+    scores.sort_by_key(|&(_, score)| if player == 1 { -score } else { score });
+    println!("Player#{} to play, score {}\n", player, result.score);
+    for ((card, y, x), score) in scores {
+        let c = &game.deck(player)[card];
+        println!(
+            "{}{}{} {:?}({}{}{}{}): {}",
+            card + 1,
+            y + 1,
+            x + 1,
+            c.name,
+            c.top,
+            c.right,
+            c.bottom,
+            c.left,
+            score
+        );
+    }
+    println!(
+        "\n{} nodes in {} ms{}",
+        result.stats.nodes,
+        result.elapsed.as_millis(),
+        if result.complete {
+            ""
+        } else {
+            ", stopped early"
+        }
     );
-    flush!();
+}
 
-    // take input
-    let mut deck_types = String::new();
-    input!(
-        deck_types,
-        "You did not enter the numbers in correct format!"
+// plays AI against AI and counts the wins of each side
+fn arena(cli: &Cli) {
+    let seat = |given: Option<Controller>| match given {
+        Some(Controller::Ai(difficulty)) => cli.tune(difficulty),
+        Some(Controller::Human) => fail("The arena only plays AI against AI"),
+        None => cli.difficulty(),
+    };
+    let (blue, red) = (seat(cli.blue), seat(cli.red));
+    let mut rng = rng(cli);
+    let mut wins = (0, 0);
+
+    for i in 0..cli.games {
+        // random decks unless they are given
+        let units = cli.decks.unwrap_or_else(|| {
+            let mut units = [Unit::Warden; 4];
+            for unit in units.iter_mut() {
+                *unit = Unit::from_digit(rng.below(9) as u32 + 1).unwrap();
+            }
+            units
+        });
+
+        let mut game = GameState::new(units);
+        while !game.is_over() {
+            let difficulty = if game.current_player() == 1 {
+                &blue
+            } else {
+                &red
+            };
+            let (card, position) = think(
+                &mut game,
+                difficulty,
+                &cli.options(difficulty),
+                &mut rng,
+                false,
+            );
+            game.apply_move(card, position)
+                .expect("AI played an illegal move !");
+        }
+
+        let (p1, p2) = game.scores();
+        if p1 > p2 {
+            wins.0 += 1;
+        } else {
+            wins.1 += 1;
+        }
+        println!("Game {}: Blue {} - {} Red with {:?}", i + 1, p1, p2, units);
+    }
+
+    println!(
+        "\nBlue ({}) won {}, Red ({}) won {}",
+        blue.name, wins.0, red.name, wins.1
     );
+}
 
-    // count which deck to insert
-    let mut count: u8 = 0;
-    // create decks for each player based on input
-    for d in deck_types.trim().split(' ') {
-        // map input to determine card unit type
-        let unit: Unit = match d {
-            "1" => Unit::Warden,
-            "2" => Unit::Keeper,
-            "3" => Unit::Siren,
-            "4" => Unit::Saboteur,
-            "5" => Unit::Ravager,
-            "6" => Unit::Titan,
-            "7" => Unit::Slayer,
-            "8" => Unit::Swarm,
-            "9" => Unit::Lancer,
-            _ => panic!("Invalid deck digit!"),
-        };
-
-        // add to first player's deck
-        if count < 2 {
-            Card::add_to_deck(&mut deck1, unit, 1);
-        }
-        // add to second player's deck
-        else {
-            Card::add_to_deck(&mut deck2, unit, 2);
+// searches the openings of the decks given with --decks, or of every deck combination, and
+// adds them to the book at the given path
+fn book(cli: &Cli) {
+    let path = cli
+        .rest
+        .first()
+        .unwrap_or_else(|| fail("Enter the path of the book"));
+    let plies: u8 = cli.rest.get(1).map_or(4, |n| {
+        n.parse()
+            .unwrap_or_else(|_| fail("Enter the plies as a number up to 255"))
+    });
+    let width: usize = cli.rest.get(2).map_or(3, |n| {
+        n.parse()
+            .unwrap_or_else(|_| fail("Enter the width as a number"))
+    });
+
+    // an existing book is extended
+    let mut book = if fs::metadata(path).is_ok() {
+        OpeningBook::load(path).unwrap_or_else(|e| fail(&e))
+    } else {
+        OpeningBook::default()
+    };
+    // deeper than the live search unless --depth says otherwise
+    let options = SearchOptions {
+        depth: Some(cli.depth.unwrap_or(book::DEFAULT_DEPTH)),
+        book: None,
+        ..cli.options(&cli.difficulty())
+    };
+
+    let combinations: Vec<[Unit; 4]> = match cli.decks {
+        Some(units) => vec![units],
+        None => (0..9usize.pow(4))
+            .map(|i| {
+                let mut units = [Unit::Warden; 4];
+                for (j, unit) in units.iter_mut().enumerate() {
+                    *unit = Unit::from_digit((i / 9usize.pow(j as u32) % 9) as u32 + 1).unwrap();
+                }
+                units
+            })
+            .collect(),
+    };
+    for (i, units) in combinations.iter().enumerate() {
+        let start = Instant::now();
+        book.generate(*units, plies, width, &options);
+        println!(
+            "{}/{} {:?}: {} positions in the book after {} ms",
+            i + 1,
+            combinations.len(),
+            units,
+            book.len(),
+            start.elapsed().as_millis()
+        );
+
+        // saved as it grows so a long run can be stopped
+        if let Err(e) = book.save(path) {
+            fail(&format!("Could not save {}: {}", path, e));
         }
+    }
+}
 
-        count += 1;
+// shows every position of a game given as moves and what each move did
+fn replay(cli: &Cli) {
+    let (mut game, moves) = replayed(cli);
+
+    show_board(&game.board, &game.bombs, &Default::default());
+    for (ply, (card, position)) in moves.into_iter().enumerate() {
+        let player = game.current_player();
+        let unit = game.deck(player).get(card).map(|c| c.name);
+        let outcome = game
+            .apply_move(card, position)
+            .unwrap_or_else(|e| fail(&format!("Move {}: {}", ply + 1, e)));
+
+        println!(
+            "\nMove {}: Player#{} placed a {:?} on {}, {}",
+            ply + 1,
+            player,
+            unit.unwrap_or(Unit::Warden),
+            position.0 + 1,
+            position.1 + 1
+        );
+        show_outcome(&outcome);
+        println!();
+        show_board(&game.board, &game.bombs, &outcome.changes());
     }
 
-    // init Empty board (height 4, width 5)
-    let mut board: [[Option<Card>; 5]; 4] = Default::default();
+    let (p1, p2) = game.scores();
+    println!("\nThe score is {} to {}", p1, p2);
 
-    // array holding bombs
-    let mut bombs: [[u8; 5]; 4] = [[0; 5]; 4];
+    if let Some(path) = &cli.html {
+        match fs::write(path, export::game_html(&game.positions())) {
+            Ok(()) => println!("Saved the game to {}", path),
+            Err(e) => fail(&format!("Could not save the game: {}", e)),
+        }
+    }
+}
 
-    // keep track of turns
-    let mut turn: u8 = 0;
+// plays a game in the terminal, the AI plays when asked with an empty input or on its own seats
+fn play(cli: &Cli) {
+    let units = decks(cli, &cli.rest);
+    let seats = cli
+        .seats()
+        .unwrap_or_else(|| [prompt_seat("Blue"), prompt_seat("Red")]);
+    // humans can still ask the AI to play their move
+    let difficulty = if seats.contains(&Controller::Human) {
+        cli.tune(cli.difficulty.unwrap_or_else(prompt_difficulty))
+    } else {
+        cli.difficulty()
+    };
+    // weaker levels pick among the best moves at random
+    let mut rng = rng(cli);
 
-    // keep track of last move (board, bombs, card_ind, card)
-    let mut prev_state: Option<([[Option<Card>; 5]; 4], [[u8; 5]; 4], usize, Card)> =
-        Default::default();
+    // create decks for each player based on input
+    let mut game = GameState::new(units);
+    // entered moves are compared to the best move before being played
+    let mut blunder_check = false;
+    // cells changed by the last move
+    let mut changes: [[CellChange; 5]; 4] = Default::default();
 
     println!("** Board is shown as (y,x) and the number of bombs that the cell holds **");
 
@@ -88,12 +477,12 @@ fn main() {
     loop {
         println!();
         // show board
-        show_board(&board, &bombs);
+        show_board(&game.board, &game.bombs, &changes);
 
         // if there are no more cards, end the game!
-        if deck1.len() + deck2.len() == 0 {
+        if game.is_over() {
             // show scores and the winner
-            let scoreboard = calc_scores(&board);
+            let scoreboard = game.scores();
             println!(
                 "\n
             ************************************
@@ -117,121 +506,152 @@ fn main() {
 
         println!();
         // show player1 deck
-        show_deck(&deck1, 1);
+        show_deck(&game.deck1, 1);
         // show player2 deck
-        show_deck(&deck2, 2);
+        show_deck(&game.deck2, 2);
         println!();
 
         // determine current turn's player
-        let current_turn = (turn % 2) + 1;
+        let current_turn = game.current_player();
 
-        // prompt move input
-        print!(
-            "It's Player#{}'s turn. Choose Card & Place (CardIndexYX e.g. 111): ",
-            current_turn
-        );
-        flush!();
-
-        // take move input
+        // take move input, the AI plays its own seats without asking
         let mut player_move = String::new();
-        input!(player_move, "Invalid move input!");
+        let seat = seats[current_turn as usize - 1];
+        if let Controller::Ai(difficulty) = seat {
+            println!(
+                "It's Player#{}'s turn (AI {})",
+                current_turn, difficulty.name
+            );
+        } else {
+            print!(
+                "It's Player#{}'s turn. Choose Card & Place (CardIndexYX e.g. 111): ",
+                current_turn
+            );
+            flush!();
+            input!(player_move, "Invalid move input!");
+        }
         let player_move = player_move.trim();
 
         // undo move
-        if player_move == "b" && turn > 0 {
-            if prev_state.is_some() {
+        if player_move == "b" {
+            if game.undo() {
+                changes = Default::default();
                 println!("\nUndoing move ...\n");
-                let state = prev_state.take().unwrap();
-                board = state.0;
-                bombs = state.1;
-                // previous turn was player1's
-                if current_turn == 2 {
-                    deck1.insert(state.2, state.3);
-                }
-                // previous turn was player2's
-                else {
-                    deck2.insert(state.2, state.3);
+            }
+        }
+        // turn the check of entered moves on or off
+        else if player_move == "c" {
+            blunder_check = !blunder_check;
+            println!(
+                "\nBlunder check {}",
+                if blunder_check { "on" } else { "off" }
+            );
+        }
+        // save the position as an image and the game as a page
+        else if player_move == "e" {
+            let svg = export::position_svg(&game.board, &game.bombs);
+            let html = export::game_html(&game.positions());
+            match fs::write("legions.svg", svg).and_then(|_| fs::write("legions.html", html)) {
+                Ok(()) => {
+                    println!("\nSaved the position to legions.svg and the game to legions.html")
                 }
-
-                turn -= 1;
-                continue;
+                Err(e) => println!("\nCould not save the game: {} !", e),
             }
         }
+        // recommend a move and explain it
+        else if player_move == "h" {
+            println!("\nThinking ...");
+            let hint = game.hint(&cli.options(&Difficulty::default()));
+            show_hint(&hint, current_turn);
+        }
         // ai should play
         else if player_move.is_empty() {
-            let mut ai_move = ai(&mut board, &mut deck1, &mut deck2, current_turn, &mut bombs);
+            let difficulty = match seat {
+                Controller::Ai(difficulty) => difficulty,
+                Controller::Human => difficulty,
+            };
+            let options = cli.options(&difficulty);
+            let (card_ind, (y, x)) = think(&mut game, &difficulty, &options, &mut rng, true);
 
             // fetch a copy of the played card
-            let prev_card = {
-                if current_turn == 1 {
-                    Card::copy(&deck1[ai_move.0])
-                } else {
-                    Card::copy(&deck2[ai_move.0])
-                }
-            };
+            let card = &game.deck(current_turn)[card_ind];
 
             // announce AI move
             println!(
                 "\nAI placed card a {:?}({}{}{}{}) on {}, {}\n",
-                prev_card.name,
-                prev_card.top,
-                prev_card.right,
-                prev_card.bottom,
-                prev_card.left,
-                ai_move.1 + 1,
-                ai_move.2 + 1
+                card.name,
+                card.top,
+                card.right,
+                card.bottom,
+                card.left,
+                y + 1,
+                x + 1
             );
 
-            // save prev state
-            prev_state = Some((copy_board(&board), bombs.clone(), ai_move.0, prev_card));
-
-            Card::place_card(
-                &mut board,
-                &mut deck1,
-                &mut deck2,
-                ai_move.0,
-                (ai_move.1, ai_move.2),
-                current_turn,
-                &mut bombs,
-                &mut ai_move.3,
-            );
+            let outcome = game
+                .apply_move(card_ind, (y, x))
+                .expect("AI played an illegal move !");
+            show_outcome(&outcome);
+            changes = outcome.changes();
+
+            // let the moves of an AI seat be followed
+            if let (Controller::Ai(_), Some(delay)) = (seat, cli.delay) {
+                thread::sleep(delay);
+            }
+            // think about the replies while a human opponent does
+            if seats[game.current_player() as usize - 1] == Controller::Human {
+                game.ponder(&options);
+            }
         }
         // player should move so apply player move on the board
         else {
-            // determine player's card and move
-            let player_move = parse_player_move(player_move);
+            // determine player's card and move, if we can't place the card the move is prompted again
+            let checked = parse_player_move(player_move).and_then(|(card_ind, position)| {
+                if !blunder_check {
+                    return Ok(Some((card_ind, position)));
+                }
 
-            // save prev state
-            let prev_card = {
-                if current_turn == 1 {
-                    Card::copy(&deck1[player_move.0])
+                // warn before playing a move worse than the best one
+                println!("\nChecking the move ...");
+                let options = cli.options(&Difficulty::default());
+                let blunder = match game.check_blunder(card_ind, position, &options)? {
+                    Some(blunder) => blunder,
+                    None => return Ok(Some((card_ind, position))),
+                };
+                let (card, y, x) = blunder.best;
+                if blunder.gives_up_a_win(current_turn) {
+                    print!("This gives up a won game");
                 } else {
-                    Card::copy(&deck2[player_move.0])
+                    print!("This loses {} point(s)", blunder.loss(current_turn));
                 }
-            };
-            let card = prev_card.name;
-            prev_state = Some((copy_board(&board), bombs.clone(), player_move.0, prev_card));
-
-            // fetch neighbours of this move
-            let mut neighbours =
-                Card::get_neighbours(&board, player_move.1 .0, player_move.1 .1, card);
-
-            // if we can't place the card, prompt for move again
-            if !Card::place_card(
-                &mut board,
-                &mut deck1,
-                &mut deck2,
-                player_move.0,
-                player_move.1,
-                current_turn,
-                &mut bombs,
-                &mut neighbours,
-            ) {
-                continue;
+                print!(
+                    " versus {}{}{}. Play it anyway? (y/N): ",
+                    card + 1,
+                    y + 1,
+                    x + 1
+                );
+                flush!();
+
+                let mut answer = String::new();
+                input!(answer, "Invalid answer input!");
+                if answer.trim().eq_ignore_ascii_case("y") {
+                    Ok(Some((card_ind, position)))
+                } else {
+                    Ok(None)
+                }
+            });
+
+            match checked.and_then(|checked| match checked {
+                Some((card_ind, position)) => Ok(Some(game.apply_move(card_ind, position)?)),
+                None => Ok(None),
+            }) {
+                Ok(Some(outcome)) => {
+                    show_outcome(&outcome);
+                    changes = outcome.changes();
+                }
+                Ok(None) => println!("\nMove cancelled"),
+                Err(e) => println!("\n{} !", e),
             }
         }
-
-        // next turn
-        turn += 1;
     }
 }
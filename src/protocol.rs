@@ -0,0 +1,478 @@
+use std::{
+    cmp::Reverse,
+    convert::TryFrom,
+    io::{BufRead, Write},
+    time::Duration,
+};
+
+use crate::ai::{SearchOptions, SearchResult};
+use crate::card::*;
+use crate::game::GameState;
+use crate::json::Value;
+
+// serializes a card with all of its fields
+pub fn card_json(card: &Card) -> Value {
+    return Value::object(vec![
+        ("name", format!("{:?}", card.name).into()),
+        ("top", card.top.into()),
+        ("right", card.right.into()),
+        ("bottom", card.bottom.into()),
+        ("left", card.left.into()),
+        ("player", card.player.into()),
+    ]);
+}
+
+// serializes the whole game state
+pub fn state_json(game: &GameState) -> Value {
+    let board: Vec<Value> = game
+        .board
+        .iter()
+        .map(|row| {
+            Value::Array(
+                row.iter()
+                    .map(|cell| match cell {
+                        Some(card) => card_json(card),
+                        None => Value::Null,
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    let bombs: Vec<Value> = game.bombs.iter().map(|row| row.to_vec().into()).collect();
+    let (p1, p2) = game.scores();
+
+    return Value::object(vec![
+        ("board", Value::Array(board)),
+        ("bombs", Value::Array(bombs)),
+        (
+            "deck1",
+            Value::Array(game.deck1.iter().map(card_json).collect()),
+        ),
+        (
+            "deck2",
+            Value::Array(game.deck2.iter().map(card_json).collect()),
+        ),
+        ("scores", vec![p1, p2].into()),
+        ("turn", game.turn.into()),
+        ("player", game.current_player().into()),
+        ("over", game.is_over().into()),
+    ]);
+}
+
+// serializes a move as 0 based (card, row, column)
+pub fn move_json(card: usize, row: usize, column: usize) -> Value {
+    return Value::object(vec![
+        ("card", card.into()),
+        ("row", row.into()),
+        ("col", column.into()),
+    ]);
+}
+
+fn position_json(position: Position) -> Value {
+    return vec![position.0, position.1].into();
+}
+
+// serializes a single event of a move, positions are 0 based [row, col]
+pub fn event_json(event: &Event) -> Value {
+    match *event {
+        Event::PlacedCard { cell, unit, player } => Value::object(vec![
+            ("event", "PlacedCard".into()),
+            ("cell", position_json(cell)),
+            ("unit", format!("{:?}", unit).into()),
+            ("player", player.into()),
+        ]),
+        Event::BombDetonated { cell, damage } => Value::object(vec![
+            ("event", "BombDetonated".into()),
+            ("cell", position_json(cell)),
+            ("damage", damage.into()),
+        ]),
+        Event::Pulled { from, to, damage } => Value::object(vec![
+            ("event", "Pulled".into()),
+            ("from", position_json(from)),
+            ("to", position_json(to)),
+            ("damage", damage.into()),
+        ]),
+        Event::Flipped { cell } => Value::object(vec![
+            ("event", "Flipped".into()),
+            ("cell", position_json(cell)),
+        ]),
+        Event::Battle {
+            attacker,
+            defender,
+            values,
+            result,
+        } => Value::object(vec![
+            ("event", "Battle".into()),
+            ("attacker", position_json(attacker)),
+            ("defender", position_json(defender)),
+            ("values", vec![values.0, values.1].into()),
+            ("result", format!("{:?}", result).into()),
+        ]),
+        Event::Captured { cell, player, via } => Value::object(vec![
+            ("event", "Captured".into()),
+            ("cell", position_json(cell)),
+            ("player", player.into()),
+            ("via", format!("{:?}", via).into()),
+        ]),
+        Event::RavagerUpgraded { cell } => Value::object(vec![
+            ("event", "RavagerUpgraded".into()),
+            ("cell", position_json(cell)),
+        ]),
+    }
+}
+
+// serializes what happened when a card was placed
+pub fn outcome_json(outcome: &MoveOutcome) -> Value {
+    return Value::object(vec![
+        ("captures", outcome.captures.into()),
+        ("same_captures", outcome.same_captures.into()),
+        ("combo_captures", outcome.combo_captures.into()),
+        ("pierce_captures", outcome.pierce_captures.into()),
+        ("total_captures", outcome.total_captures().into()),
+        ("pulls", outcome.pulls.into()),
+        ("flips", outcome.flips.into()),
+        ("bomb_damage", outcome.bomb_damage.into()),
+        (
+            "events",
+            match &outcome.events {
+                Some(events) => Value::Array(events.iter().map(event_json).collect()),
+                None => Value::Null,
+            },
+        ),
+    ]);
+}
+
+// serializes the statistics of a search
+pub fn stats_json(result: &SearchResult) -> Value {
+    return Value::object(vec![
+        ("score", result.score.into()),
+        ("moves", result.scores.len().into()),
+        ("nodes", result.stats.nodes.into()),
+        ("quiescence_nodes", result.stats.quiescence_nodes.into()),
+        ("cutoffs", result.stats.cutoffs.into()),
+        ("tt_hits", result.stats.tt_hits.into()),
+        ("max_depth", result.stats.max_depth.into()),
+        ("elapsed_ms", (result.elapsed.as_millis() as u64).into()),
+        ("complete", result.complete.into()),
+        (
+            "nodes_per_second",
+            Value::Number(result.nodes_per_second().round()),
+        ),
+    ]);
+}
+
+// builds a failed response holding the message
+pub fn error(msg: &str) -> Value {
+    return Value::object(vec![("ok", false.into()), ("error", msg.into())]);
+}
+
+fn field(request: &Value, key: &str) -> Result<usize, String> {
+    return request
+        .get(key)
+        .and_then(|v| v.as_u64())
+        .and_then(|v| usize::try_from(v).ok())
+        .ok_or(format!("Missing or invalid \"{}\"", key));
+}
+
+// parses the "decks" field of a request into unit types
+fn parse_decks(request: &Value) -> Result<[Unit; 4], String> {
+    let decks = request
+        .get("decks")
+        .and_then(|d| d.as_array())
+        .ok_or("Missing \"decks\" array")?;
+
+    if decks.len() != 4 {
+        return Err("\"decks\" must hold exactly 4 unit types".to_string());
+    }
+
+    let mut units = [Unit::Warden; 4];
+    for i in 0..4 {
+        units[i] = decks[i]
+            .as_u64()
+            .and_then(|d| u32::try_from(d).ok())
+            .and_then(Unit::from_digit)
+            .ok_or("Invalid deck digit!")?;
+    }
+
+    return Ok(units);
+}
+
+// searches the current position, stopping after "time_ms" milliseconds when given
+// and picking among equally good moves with "seed"
+fn search(game: &mut GameState, request: &Value) -> SearchResult {
+    let options = SearchOptions {
+        seed: request.get("seed").and_then(|s| s.as_u64()),
+        time: request
+            .get("time_ms")
+            .and_then(|t| t.as_u64())
+            .map(Duration::from_millis),
+        ..Default::default()
+    };
+    return game.best_move(&options);
+}
+
+// handles a single request and returns its response
+pub fn handle(game: &mut Option<GameState>, request: &Value) -> Value {
+    let cmd = match request.get("cmd").and_then(|c| c.as_str()) {
+        Some(cmd) => cmd,
+        None => return error("Missing \"cmd\""),
+    };
+
+    // a new game can be started at any time
+    if cmd == "new" {
+        return match parse_decks(request) {
+            Ok(units) => {
+                let g = GameState::new(units);
+                let response = Value::object(vec![("ok", true.into()), ("state", state_json(&g))]);
+                *game = Some(g);
+                response
+            }
+            Err(e) => error(&e),
+        };
+    }
+
+    let game = match game {
+        Some(g) => g,
+        None => return error("No game in progress, send \"new\" first"),
+    };
+
+    match cmd {
+        "state" => Value::object(vec![("ok", true.into()), ("state", state_json(game))]),
+        "move" => {
+            let (card, row, column) = match (
+                field(request, "card"),
+                field(request, "row"),
+                field(request, "col"),
+            ) {
+                (Ok(c), Ok(r), Ok(x)) => (c, r, x),
+                (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return error(&e),
+            };
+
+            if game.is_over() {
+                return error("The game is over");
+            }
+            let outcome = match game.apply_move(card, (row, column)) {
+                Ok(o) => o,
+                Err(e) => return error(&e.to_string()),
+            };
+
+            Value::object(vec![
+                ("ok", true.into()),
+                ("outcome", outcome_json(&outcome)),
+                ("state", state_json(game)),
+            ])
+        }
+        "best" => {
+            if game.is_over() {
+                return error("The game is over");
+            }
+
+            let result = search(game, request);
+            let (card, row, column, _) = result.best;
+            let chosen = move_json(card, row, column);
+
+            // optionally play the chosen move right away
+            let outcome = if request.get("apply").and_then(|a| a.as_bool()) == Some(true) {
+                let outcome = game
+                    .apply_move(card, (row, column))
+                    .expect("AI played an illegal move !");
+                outcome_json(&outcome)
+            } else {
+                Value::Null
+            };
+
+            Value::object(vec![
+                ("ok", true.into()),
+                ("move", chosen),
+                ("outcome", outcome),
+                ("stats", stats_json(&result)),
+                ("state", state_json(game)),
+            ])
+        }
+        "analyse" => {
+            if game.is_over() {
+                return error("The game is over");
+            }
+
+            let result = search(game, request);
+
+            // best moves of the current player come first
+            let mut scores = result.scores.clone();
+            if game.current_player() == 1 {
+                scores.sort_by_key(|s| Reverse(s.1));
+            } else {
+                scores.sort_by_key(|s| s.1);
+            }
+            let moves: Vec<Value> = scores
+                .iter()
+                .map(|&((card, row, column), score)| {
+                    Value::object(vec![
+                        ("move", move_json(card, row, column)),
+                        ("score", score.into()),
+                    ])
+                })
+                .collect();
+
+            Value::object(vec![
+                ("ok", true.into()),
+                ("moves", Value::Array(moves)),
+                ("stats", stats_json(&result)),
+            ])
+        }
+        "undo" => {
+            if !game.undo() {
+                return error("There is no move to undo");
+            }
+            Value::object(vec![("ok", true.into()), ("state", state_json(game))])
+        }
+        _ => error(&format!("Unknown command \"{}\"", cmd)),
+    }
+}
+
+// runs the JSON-lines protocol: one request per input line, one response per output line
+pub fn run(input: impl BufRead, mut output: impl Write) {
+    let mut game: Option<GameState> = None;
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match Value::parse(&line) {
+            Ok(request) => handle(&mut game, &request),
+            Err(e) => error(&e),
+        };
+
+        writeln!(output, "{}", response).expect("Could not write response !");
+        output.flush().expect("Could not flush response !");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // runs the protocol over the request lines and parses every response line
+    fn session(requests: &[String]) -> Vec<Value> {
+        let mut output = Vec::new();
+        run(Cursor::new(requests.join("\n")), &mut output);
+        return String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| Value::parse(line).unwrap())
+            .collect();
+    }
+
+    fn ok(response: &Value) -> bool {
+        return response.get("ok").and_then(|o| o.as_bool()) == Some(true);
+    }
+
+    fn cards_left(response: &Value) -> usize {
+        let state = response.get("state").unwrap();
+        return ["deck1", "deck2"]
+            .iter()
+            .map(|deck| state.get(deck).and_then(|d| d.as_array()).unwrap().len())
+            .sum();
+    }
+
+    #[test]
+    fn games_are_played_through_requests() {
+        let mut requests = vec!["{\"cmd\":\"new\",\"decks\":[6,3,8,1]}".to_string()];
+
+        // the first legal moves, followed on a local game, until the search is short
+        let mut local = GameState::new([Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Warden]);
+        for _ in 0..12 {
+            let (card, y, x) = local.perft(1)[0].0;
+            local.apply_move(card, (y, x)).unwrap();
+            requests.push(format!(
+                "{{\"cmd\":\"move\",\"card\":{},\"row\":{},\"col\":{}}}",
+                card, y, x
+            ));
+        }
+        requests.push("".to_string());
+        for request in [
+            "{\"cmd\":\"state\"}",
+            "{\"cmd\":\"analyse\",\"seed\":1}",
+            "{\"cmd\":\"best\",\"seed\":1}",
+            "{\"cmd\":\"best\",\"seed\":1,\"apply\":true}",
+            "{\"cmd\":\"undo\"}",
+        ] {
+            requests.push(request.to_string());
+        }
+
+        // blank lines get no response
+        let responses = session(&requests);
+        assert_eq!(responses.len(), requests.len() - 1);
+        assert!(responses.iter().all(ok), "{:?}", responses);
+
+        assert_eq!(cards_left(&responses[0]), 16);
+        let events = responses[1].get("outcome").and_then(|o| o.get("events"));
+        assert!(events.and_then(|e| e.as_array()).is_some());
+        assert_eq!(cards_left(&responses[12]), 4);
+
+        let state = &responses[13];
+        assert_eq!(cards_left(state), 4);
+        let turn = state.get("state").and_then(|s| s.get("turn")).unwrap();
+        assert_eq!(turn.as_u64(), Some(local.turn as u64));
+
+        // every move is scored and the best one comes first
+        let moves = responses[14]
+            .get("moves")
+            .and_then(|m| m.as_array())
+            .unwrap();
+        assert_eq!(moves.len(), local.perft(1).len());
+        let best = responses[15].get("move").unwrap();
+        assert_eq!(moves[0].get("move"), Some(best));
+        assert_eq!(responses[15].get("outcome"), Some(&Value::Null));
+        assert_eq!(cards_left(&responses[15]), 4);
+
+        // the applied move is taken back
+        assert_eq!(responses[16].get("move"), Some(best));
+        assert_eq!(cards_left(&responses[16]), 3);
+        assert_eq!(cards_left(&responses[17]), 4);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let requests: Vec<String> = [
+            "{\"cmd\":\"state\"}",
+            "not json",
+            "{\"decks\":[1,2,3,4]}",
+            "{\"cmd\":\"new\",\"decks\":[1,2,3]}",
+            "{\"cmd\":\"new\",\"decks\":[1,2,3,0]}",
+            "{\"cmd\":\"new\",\"decks\":[1,2,3,4294967297]}",
+            "{\"cmd\":\"new\",\"decks\":[1,2,3,4]}",
+            "{\"cmd\":\"move\",\"card\":0,\"row\":0}",
+            "{\"cmd\":\"move\",\"card\":10,\"row\":0,\"col\":0}",
+            "{\"cmd\":\"undo\"}",
+            "{\"cmd\":\"fly\"}",
+        ]
+        .iter()
+        .map(|r| r.to_string())
+        .collect();
+
+        let responses = session(&requests);
+        let errors: Vec<Option<&str>> = responses
+            .iter()
+            .map(|r| r.get("error").and_then(|e| e.as_str()))
+            .collect();
+        assert_eq!(errors[0], Some("No game in progress, send \"new\" first"));
+        assert!(errors[1].is_some());
+        assert_eq!(errors[2], Some("Missing \"cmd\""));
+        assert_eq!(errors[3], Some("\"decks\" must hold exactly 4 unit types"));
+        assert_eq!(errors[4], Some("Invalid deck digit!"));
+        // not a digit even though it would truncate to one
+        assert_eq!(errors[5], Some("Invalid deck digit!"));
+        assert!(ok(&responses[6]));
+        assert_eq!(errors[7], Some("Missing or invalid \"col\""));
+        assert!(errors[8].is_some());
+        assert_eq!(errors[9], Some("There is no move to undo"));
+        assert_eq!(errors[10], Some("Unknown command \"fly\""));
+        assert!(responses.iter().enumerate().all(|(i, r)| ok(r) == (i == 6)));
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::game::GameState;
+use crate::json::Value;
+use crate::protocol::{error, handle};
+
+// games being played on the server with the last time they were used, each locked separately so
+// searches don't block other games
+type Games = Arc<Mutex<HashMap<u64, (Instant, Arc<Mutex<Option<GameState>>>)>>>;
+
+// each game holds its own transposition table, so their number is bounded and the ones nobody
+// used for a while make room for new games
+const MAX_GAMES: usize = 32;
+const IDLE_EXPIRY: Duration = Duration::from_secs(30 * 60);
+
+// a parsed HTTP request
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+// limits on what a client may send, above them the request is refused without reading the rest
+const MAX_BODY: usize = 64 * 1024;
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+// each connection has its own thread, so slow clients are cut off and only so many are served at
+// once, the others are refused right away
+const MAX_CONNECTIONS: usize = 16;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// reads the stream until the deadline, however slowly the client sends the request
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        return self.stream.read(buf);
+    }
+}
+
+// counts a connection being served until it is dropped
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// the (status, error) replied when reading the request failed
+fn read_error(e: io::Error) -> (&'static str, &'static str) {
+    return match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+            ("408 Request Timeout", "Request not received in time")
+        }
+        _ => ("400 Bad Request", "Malformed request"),
+    };
+}
+
+// reads a line of at most MAX_LINE bytes, an empty string at the end of the stream
+fn read_line(reader: &mut impl BufRead) -> Result<String, (&'static str, &'static str)> {
+    let mut line = String::new();
+    reader
+        .take(MAX_LINE as u64 + 1)
+        .read_line(&mut line)
+        .map_err(read_error)?;
+    if line.len() > MAX_LINE {
+        return Err((
+            "431 Request Header Fields Too Large",
+            "Request line or header too long",
+        ));
+    }
+    return Ok(line);
+}
+
+// reads a request (request line, headers and body) from the stream within REQUEST_TIMEOUT, or
+// the (status, error) to reply with
+fn read_request(stream: &TcpStream) -> Result<Request, (&'static str, &'static str)> {
+    let malformed = ("400 Bad Request", "Malformed request");
+    let mut reader = BufReader::new(Deadline {
+        stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+
+    let line = read_line(&mut reader)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or(malformed)?.to_string();
+    let path = parts.next().ok_or(malformed)?.to_string();
+
+    // headers, only the content length is of interest
+    let mut content_length: usize = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_line(&mut reader)?;
+        if header.is_empty() {
+            break;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(("431 Request Header Fields Too Large", "Too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| malformed)?;
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(("413 Payload Too Large", "Request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+
+    return Ok(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).to_string(),
+    });
+}
+
+// writes a JSON response
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // the client may have gone away, there is nobody to report it to
+    let _ = stream.write_all(response.as_bytes());
+}
+
+// adds the query parameters and the command to the request body so it can be handled by the
+// protocol, a parameter holding a JSON value (e.g. time_ms=500) is read as that value
+fn with_cmd(body: &str, query: &str, cmd: &str) -> Result<Value, String> {
+    let mut request = if body.trim().is_empty() {
+        Value::Object(Vec::new())
+    } else {
+        Value::parse(body)?
+    };
+
+    match &mut request {
+        Value::Object(pairs) => {
+            for parameter in query.split('&').filter(|p| !p.is_empty()) {
+                let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+                let value = Value::parse(value).unwrap_or_else(|_| value.into());
+                pairs.retain(|(k, _)| k != key);
+                pairs.push((key.to_string(), value));
+            }
+            pairs.retain(|(k, _)| k != "cmd");
+            pairs.push(("cmd".to_string(), cmd.into()));
+        }
+        _ => return Err("Request body must be a JSON object".to_string()),
+    }
+
+    return Ok(request);
+}
+
+// routes a request to the matching game and command, returning (status, body)
+fn route(games: &Games, next_id: &Mutex<u64>, request: &Request) -> (&'static str, Value) {
+    let (path, query) = request.path.split_once('?').unwrap_or((&request.path, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    // (game id, command) of the request
+    let (id, cmd) = match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["games"]) => (None, "new"),
+        ("GET", ["games", id]) => (Some(*id), "state"),
+        ("POST", ["games", id, "moves"]) => (Some(*id), "move"),
+        ("POST", ["games", id, "best"]) => (Some(*id), "best"),
+        ("GET", ["games", id, "analysis"]) => (Some(*id), "analyse"),
+        ("POST", ["games", id, "undo"]) => (Some(*id), "undo"),
+        _ => return ("404 Not Found", error("Unknown endpoint")),
+    };
+
+    let body = match with_cmd(&request.body, query, cmd) {
+        Ok(b) => b,
+        Err(e) => return ("400 Bad Request", error(&e)),
+    };
+
+    let (id, game) = match id {
+        // create a new game
+        None => {
+            let mut games = games.lock().unwrap();
+            games.retain(|_, (used, _)| used.elapsed() < IDLE_EXPIRY);
+            if games.len() >= MAX_GAMES {
+                return (
+                    "503 Service Unavailable",
+                    error("Too many games, try again later"),
+                );
+            }
+
+            let mut next_id = next_id.lock().unwrap();
+            *next_id += 1;
+            let game = Arc::new(Mutex::new(None));
+            games.insert(*next_id, (Instant::now(), game.clone()));
+            (*next_id, game)
+        }
+        // fetch an existing game
+        Some(id) => {
+            let game = id.parse::<u64>().ok().and_then(|id| {
+                let mut games = games.lock().unwrap();
+                let (used, game) = games.get_mut(&id)?;
+                *used = Instant::now();
+                Some((id, game.clone()))
+            });
+            match game {
+                Some(g) => g,
+                None => return ("404 Not Found", error("Unknown game")),
+            }
+        }
+    };
+
+    let mut response = handle(&mut game.lock().unwrap(), &body);
+
+    // a game that failed to start is discarded
+    if cmd == "new" && response.get("ok") != Some(&Value::Bool(true)) {
+        games.lock().unwrap().remove(&id);
+        return ("400 Bad Request", response);
+    }
+
+    if let Value::Object(pairs) = &mut response {
+        pairs.insert(1, ("id".to_string(), id.into()));
+    }
+
+    if response.get("ok") == Some(&Value::Bool(true)) {
+        return ("200 OK", response);
+    } else {
+        return ("400 Bad Request", response);
+    }
+}
+
+// serves the engine over HTTP on the listener, handling each connection on its own thread
+pub fn serve(listener: TcpListener) {
+    let games: Games = Default::default();
+    let next_id: Arc<Mutex<u64>> = Default::default();
+    let connections: Arc<AtomicUsize> = Default::default();
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+            continue;
+        }
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            respond(
+                &mut stream,
+                "503 Service Unavailable",
+                &error("Too many connections, try again later"),
+            );
+            continue;
+        }
+        let connection = Connection(connections.clone());
+
+        let games = games.clone();
+        let next_id = next_id.clone();
+        thread::spawn(move || {
+            let _connection = connection;
+            let request = match read_request(&stream) {
+                Ok(r) => r,
+                Err((status, message)) => {
+                    respond(&mut stream, status, &error(message));
+                    return;
+                }
+            };
+
+            // browsers send a preflight before posting JSON
+            if request.method == "OPTIONS" {
+                respond(&mut stream, "200 OK", &Value::Null);
+                return;
+            }
+
+            let (status, body) = route(&games, &next_id, &request);
+            respond(&mut stream, status, &body);
+        });
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::card::*;
+
+// what the stored score says about the real score of the position
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    // the real score is at least the stored one
+    Lower,
+    // the real score is at most the stored one
+    Upper,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Entry {
+    pub score: i8,
+    // depth left when the position was searched
+    pub depth: u8,
+    pub bound: Bound,
+    // best move found as (card, row, column)
+    pub best: Option<(usize, usize, usize)>,
+}
+
+impl Entry {
+    fn pack(&self) -> u64 {
+        let bound: u64 = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best: u64 = match self.best {
+            Some((card, y, x)) => (card as u64) | (y as u64) << 8 | (x as u64) << 16,
+            None => 0xff_ffff,
+        };
+
+        // a used slot never packs to 0
+        return 1
+            | (self.score as u8 as u64) << 8
+            | (self.depth as u64) << 16
+            | bound << 24
+            | best << 32;
+    }
+
+    fn unpack(data: u64) -> Entry {
+        let bound = match (data >> 24) & 0xff {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best = match (data >> 32) & 0xff_ffff {
+            0xff_ffff => None,
+            b => Some((
+                (b & 0xff) as usize,
+                ((b >> 8) & 0xff) as usize,
+                ((b >> 16) & 0xff) as usize,
+            )),
+        };
+
+        return Entry {
+            score: (data >> 8) as u8 as i8,
+            depth: (data >> 16) as u8,
+            bound,
+            best,
+        };
+    }
+}
+
+// scores of searched positions shared between the search threads without locking,
+// each slot stores (key ^ data, data) so a slot torn by two writers fails the key check
+pub struct TranspositionTable {
+    slots: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl TranspositionTable {
+    // a table holding 2^bits positions
+    pub fn new(bits: u8) -> TranspositionTable {
+        return TranspositionTable {
+            slots: (0..1usize << bits)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        };
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let slot = &self.slots[key as usize & (self.slots.len() - 1)];
+        let data = slot.1.load(Ordering::Relaxed);
+        if data == 0 || slot.0.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        return Some(Entry::unpack(data));
+    }
+
+    // stores the entry unless the slot holds a deeper search of the same position
+    pub fn store(&self, key: u64, entry: Entry) {
+        if let Some(old) = self.probe(key) {
+            if old.depth > entry.depth {
+                return;
+            }
+        }
+
+        let slot = &self.slots[key as usize & (self.slots.len() - 1)];
+        let data = entry.pack();
+        slot.0.store(key ^ data, Ordering::Relaxed);
+        slot.1.store(data, Ordering::Relaxed);
+    }
+
+    // forgets every stored position
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.0.store(0, Ordering::Relaxed);
+            slot.1.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // key of a position: board, bombs, both decks and the player to move
+    pub fn hash(
+        board: &[[Option<Card>; 5]; 4],
+        bombs: &[[u8; 5]; 4],
+        deck1: &[Card],
+        deck2: &[Card],
+        player: u8,
+    ) -> u64 {
+        let mut hash = mix(player as u64);
+        for i in 0..4 {
+            for j in 0..5 {
+                let card = match &board[i][j] {
+                    Some(card) => card_code(card),
+                    None => 0,
+                };
+                hash = mix(hash ^ card ^ (bombs[i][j] as u64) << 48);
+            }
+        }
+        for card in deck1.iter().chain(deck2.iter()) {
+            hash = mix(hash ^ card_code(card));
+        }
+
+        return hash;
+    }
+
+    // key of a position searched with the given plies of quiescence, so scores found with another
+    // setting are never reused
+    pub fn with_quiescence(key: u64, quiescence: u8) -> u64 {
+        if quiescence == 0 {
+            return key;
+        }
+        return mix(key ^ (quiescence as u64) << 56);
+    }
+}
+
+impl Default for TranspositionTable {
+    // 2^20 positions (16 MB)
+    fn default() -> TranspositionTable {
+        return TranspositionTable::new(20);
+    }
+}
+
+// unique number of a card, never 0
+fn card_code(card: &Card) -> u64 {
+    return (card.name as u64 + 1)
+        | (card.player as u64) << 8
+        | (card.top as u64) << 16
+        | (card.right as u64) << 24
+        | (card.bottom as u64) << 32
+        | (card.left as u64) << 40;
+}
+
+// splitmix64 finalizer
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_survive_packing() {
+        let entries = [
+            Entry {
+                score: -125,
+                depth: 8,
+                bound: Bound::Upper,
+                best: Some((7, 3, 4)),
+            },
+            Entry {
+                score: 112,
+                depth: 0,
+                bound: Bound::Exact,
+                best: None,
+            },
+            Entry {
+                score: 0,
+                depth: 255,
+                bound: Bound::Lower,
+                best: Some((0, 0, 0)),
+            },
+        ];
+        for entry in entries {
+            assert_eq!(Entry::unpack(entry.pack()), entry);
+        }
+    }
+
+    #[test]
+    fn deeper_searches_are_kept() {
+        let tt = TranspositionTable::new(4);
+        let entry = Entry {
+            score: 3,
+            depth: 4,
+            bound: Bound::Exact,
+            best: Some((1, 2, 3)),
+        };
+
+        assert_eq!(tt.probe(42), None);
+        tt.store(42, entry);
+        assert_eq!(tt.probe(42), Some(entry));
+        // same slot, different position
+        assert_eq!(tt.probe(42 + 16), None);
+
+        tt.store(42, Entry { depth: 2, ..entry });
+        assert_eq!(tt.probe(42), Some(entry));
+
+        tt.clear();
+        assert_eq!(tt.probe(42), None);
+    }
+}
@@ -0,0 +1,487 @@
+use std::{
+    io::{self, stdin, stdout, Read, Write},
+    process::{Command, Stdio},
+};
+
+use crate::ai::SearchOptions;
+use crate::card::*;
+use crate::difficulty::Difficulty;
+use crate::game::GameState;
+use crate::rng::Rng;
+use crate::utility::*;
+
+// ANSI escape codes
+const RESET: &str = "\x1b[0m";
+const BLUE: &str = "\x1b[44;97m";
+const RED: &str = "\x1b[41;97m";
+const BLUE_TEXT: &str = "\x1b[94m";
+const RED_TEXT: &str = "\x1b[91m";
+const BOMB: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const CURSOR: &str = "\x1b[7m";
+
+// characters inside a cell of the grid
+const CELL_WIDTH: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Back,
+    Char(char),
+}
+
+// what the arrow keys move
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Focus {
+    Deck,
+    Board,
+}
+
+// selection of the player and the last message shown under the board
+#[derive(Clone, Debug)]
+pub struct View {
+    pub focus: Focus,
+    // selected card of the current player's deck
+    pub card: usize,
+    pub cell: Position,
+    pub message: String,
+    // cells changed by the last move
+    pub changes: [[CellChange; 5]; 4],
+}
+
+impl Default for View {
+    fn default() -> View {
+        return View {
+            focus: Focus::Deck,
+            card: 0,
+            cell: (0, 0),
+            message: String::new(),
+            changes: Default::default(),
+        };
+    }
+}
+
+// the terminal in raw mode on the alternate screen, restored when dropped
+struct Terminal {
+    saved: String,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        stdout().flush()?;
+        return Ok(Terminal {
+            saved: saved.trim().to_string(),
+        });
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// runs stty on the terminal of stdin
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed, is stdin a terminal ?"));
+    }
+    return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+}
+
+// reads a key press from raw terminal input, None at the end of the input
+pub fn read_key(bytes: &mut impl Iterator<Item = u8>) -> Option<Key> {
+    loop {
+        let key = match bytes.next()? {
+            // escape sequences of the arrow keys, a lone escape goes back
+            0x1b => match bytes.next()? {
+                b'[' | b'O' => match bytes.next()? {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    b'C' => Key::Right,
+                    b'D' => Key::Left,
+                    _ => continue,
+                },
+                _ => Key::Back,
+            },
+            b'\r' | b'\n' | b' ' => Key::Enter,
+            0x7f | 0x08 => Key::Back,
+            // ctrl-c quits like q since raw mode turns off signals
+            0x03 => Key::Char('q'),
+            b => Key::Char(b as char),
+        };
+        return Some(key);
+    }
+}
+
+// the card with the bonus it gets on the board, swarms count their allies
+fn effective(board: &[[Option<Card>; 5]; 4], card: &Card) -> Card {
+    let mut c = Card::copy(card);
+    if c.name == Unit::Swarm {
+        c.upgrade(Card::swarm_count(board, c.player));
+    }
+    return c;
+}
+
+// the 3 lines inside a cell, what the last move did to it is marked in the bottom left corner
+fn cell_lines(game: &GameState, view: &View, y: usize, x: usize) -> [String; 3] {
+    return match &game.board[y][x] {
+        Some(card) => {
+            let c = effective(&game.board, card);
+            let name = format!("{:?}", c.name);
+            [
+                format!("{:^w$}", c.top, w = CELL_WIDTH),
+                format!(
+                    "{:<2}{:^w$}{:>2}",
+                    c.left,
+                    name,
+                    c.right,
+                    w = CELL_WIDTH - 4
+                ),
+                format!(
+                    "{:<4}{:^w$}    ",
+                    view.changes[y][x].markers(),
+                    c.bottom,
+                    w = CELL_WIDTH - 8
+                ),
+            ]
+        }
+        None => {
+            let bombs = game.bombs[y][x];
+            let marker = if bombs > 0 {
+                format!("{}x bomb", bombs)
+            } else {
+                String::new()
+            };
+            [
+                format!("{:^w$}", format!("{},{}", y + 1, x + 1), w = CELL_WIDTH),
+                format!("{:^w$}", marker, w = CELL_WIDTH),
+                " ".repeat(CELL_WIDTH),
+            ]
+        }
+    };
+}
+
+// colors of a cell for its owner, bombs and the cursor
+fn cell_style(game: &GameState, view: &View, y: usize, x: usize, line: usize) -> String {
+    let mut style = match &game.board[y][x] {
+        Some(card) if card.player == 1 => BLUE.to_string(),
+        Some(_) => RED.to_string(),
+        None if line == 0 => DIM.to_string(),
+        None => BOMB.to_string(),
+    };
+    if view.changes[y][x] != CellChange::default() {
+        style += BOLD;
+    }
+    if view.focus == Focus::Board && view.cell == (y, x) {
+        style += CURSOR;
+    }
+    return style;
+}
+
+// one player's deck, 4 cards per line, the selected card is highlighted on the current player's deck
+fn deck_lines(game: &GameState, view: &View, player: u8) -> Vec<String> {
+    let (name, color) = if player == 1 {
+        ("Blue", BLUE_TEXT)
+    } else {
+        ("Red ", RED_TEXT)
+    };
+    let current = !game.is_over() && game.current_player() == player;
+
+    let mut lines = vec![format!("{}{}{}{} ", color, BOLD, name, RESET)];
+    for (i, c) in game.deck(player).iter().enumerate() {
+        if i > 0 && i % 4 == 0 {
+            lines.push("     ".to_string());
+        }
+        let card = format!(
+            " {} {:<8} {}{}{}{} ",
+            i + 1,
+            format!("{:?}", c.name),
+            c.top,
+            c.right,
+            c.bottom,
+            c.left
+        );
+        let line = lines.last_mut().unwrap();
+        if current && i == view.card {
+            *line += &format!("{}{}{}{}", color, CURSOR, card, RESET);
+        } else {
+            *line += &format!("{}{}{}", color, card, RESET);
+        }
+    }
+    return lines;
+}
+
+// draws the whole screen as lines
+pub fn render(game: &GameState, view: &View) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let (blue, red) = game.scores();
+    lines.push(format!(
+        "{}Legions{}   {}Blue {}{} - {}{} Red{}",
+        BOLD, RESET, BLUE_TEXT, blue, RESET, RED_TEXT, red, RESET
+    ));
+    lines.push(String::new());
+
+    let border = format!("+{}", format!("{}+", "-".repeat(CELL_WIDTH)).repeat(5));
+    for y in 0..4 {
+        lines.push(border.clone());
+        for line in 0..3 {
+            let mut row = String::from("|");
+            for x in 0..5 {
+                let text = &cell_lines(game, view, y, x)[line];
+                row += &format!("{}{}{}|", cell_style(game, view, y, x, line), text, RESET);
+            }
+            lines.push(row);
+        }
+    }
+    lines.push(border);
+    lines.push(String::new());
+
+    lines.extend(deck_lines(game, view, 1));
+    lines.extend(deck_lines(game, view, 2));
+    lines.push(String::new());
+
+    if game.is_over() {
+        let winner = if blue > red { "Blue" } else { "Red" };
+        lines.push(format!("{}{} wins !{}", BOLD, winner, RESET));
+    } else {
+        let (name, color) = if game.current_player() == 1 {
+            ("Blue", BLUE_TEXT)
+        } else {
+            ("Red", RED_TEXT)
+        };
+        let task = match view.focus {
+            Focus::Deck => "choose a card",
+            Focus::Board => "choose a cell",
+        };
+        lines.push(format!("{}{}{} to play, {}", color, name, RESET, task));
+    }
+    lines.push(view.message.clone());
+
+    // explain the markers shown
+    let shown: Vec<String> = CellChange::LEGEND
+        .iter()
+        .filter(|(marker, _)| {
+            view.changes
+                .iter()
+                .flatten()
+                .any(|c| c.markers().contains(*marker))
+        })
+        .map(|(marker, meaning)| format!("{} {}", marker, meaning))
+        .collect();
+    lines.push(shown.join("  "));
+    lines.push(String::new());
+    lines.push(format!(
+        "{}arrows move  enter select  backspace back  a AI move  u undo  q quit{}",
+        DIM, RESET
+    ));
+
+    return lines;
+}
+
+// applies a key press to the game, returns false once the player quits
+pub fn handle_key(
+    game: &mut GameState,
+    view: &mut View,
+    key: Key,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+    rng: &mut Rng,
+) -> bool {
+    match key {
+        Key::Char('q') => return false,
+        Key::Char('u') => {
+            view.changes = Default::default();
+            view.message = if game.undo() {
+                "Undid the last move".to_string()
+            } else {
+                "Nothing to undo".to_string()
+            };
+            view.focus = Focus::Deck;
+        }
+        _ if game.is_over() => {}
+        Key::Char('a') => {
+            let player = game.current_player();
+            let result = game.best_move(options);
+            let (card, y, x) = difficulty.pick(&result, player, rng);
+            play(game, view, card, (y, x), "AI");
+        }
+        Key::Left | Key::Right | Key::Up | Key::Down if view.focus == Focus::Deck => {
+            let len = game.deck(game.current_player()).len();
+            view.card = match key {
+                Key::Left | Key::Up => (view.card + len - 1) % len,
+                _ => (view.card + 1) % len,
+            };
+        }
+        Key::Up => view.cell.0 = (view.cell.0 + 3) % 4,
+        Key::Down => view.cell.0 = (view.cell.0 + 1) % 4,
+        Key::Left => view.cell.1 = (view.cell.1 + 4) % 5,
+        Key::Right => view.cell.1 = (view.cell.1 + 1) % 5,
+        Key::Enter if view.focus == Focus::Deck => view.focus = Focus::Board,
+        Key::Enter => {
+            let (card, cell) = (view.card, view.cell);
+            play(game, view, card, cell, "You");
+        }
+        Key::Back => view.focus = Focus::Deck,
+        Key::Char(_) => {}
+    }
+
+    // keep the selected card within the deck of the player to move
+    if !game.is_over() {
+        let len = game.deck(game.current_player()).len();
+        view.card = view.card.min(len - 1);
+    }
+    return true;
+}
+
+fn play(game: &mut GameState, view: &mut View, card: usize, cell: Position, who: &str) {
+    let unit = game.deck(game.current_player()).get(card).map(|c| c.name);
+    match game.apply_move(card, cell) {
+        Ok(outcome) => {
+            view.message = format!(
+                "{} played a {:?} on {}, {}",
+                who,
+                unit.unwrap_or(Unit::Warden),
+                cell.0 + 1,
+                cell.1 + 1
+            );
+            view.changes = outcome.changes();
+            let summary = outcome_summary(&outcome);
+            if !summary.is_empty() {
+                view.message += &format!(": {}", summary);
+            }
+            view.focus = Focus::Deck;
+        }
+        Err(e) => view.message = format!("{} !", e),
+    }
+}
+
+// plays a game in full screen until it is quit, the AI searches with the options and picks its
+// move at the given difficulty when asked
+pub fn run(
+    game: &mut GameState,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+    rng: &mut Rng,
+) -> io::Result<()> {
+    let _terminal = Terminal::open()?;
+    let mut view = View::default();
+    let mut bytes = stdin().lock().bytes().map_while(Result::ok);
+
+    loop {
+        let frame = render(game, &view).join("\x1b[K\r\n");
+        print!("\x1b[H\x1b[2J{}", frame);
+        stdout().flush()?;
+
+        let key = match read_key(&mut bytes) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        if key == Key::Char('a') && !game.is_over() {
+            // searching takes a while
+            print!("\r\nThinking ...");
+            stdout().flush()?;
+        }
+        if !handle_key(game, &mut view, key, difficulty, options, rng) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        let mut bytes = input.iter().copied();
+        return std::iter::from_fn(|| read_key(&mut bytes)).collect();
+    }
+
+    // presses the key with a quick AI
+    fn press(game: &mut GameState, view: &mut View, key: Key) -> bool {
+        let difficulty = Difficulty::default();
+        let options = SearchOptions {
+            depth: Some((1, 1)),
+            ..difficulty.options()
+        };
+        return handle_key(game, view, key, &difficulty, &options, &mut Rng::new(1));
+    }
+
+    #[test]
+    fn keys_are_decoded() {
+        assert_eq!(
+            keys(b"\x1b[A\x1b[B\x1b[C\x1b[D\rq\x7f\x1bx"),
+            vec![
+                Key::Up,
+                Key::Down,
+                Key::Right,
+                Key::Left,
+                Key::Enter,
+                Key::Char('q'),
+                Key::Back,
+                Key::Back,
+            ]
+        );
+    }
+
+    #[test]
+    fn cards_are_placed_with_the_keys() {
+        let mut game = GameState::new([Unit::Swarm, Unit::Swarm, Unit::Titan, Unit::Warden]);
+        let mut view = View::default();
+
+        // second card on row 2, column 3
+        for key in [
+            Key::Right,
+            Key::Enter,
+            Key::Down,
+            Key::Right,
+            Key::Right,
+            Key::Enter,
+        ] {
+            assert!(press(&mut game, &mut view, key));
+        }
+        let card = game.board[1][2].as_ref().expect("no card was placed");
+        assert_eq!((card.name, card.player), (Unit::Swarm, 1));
+        assert_eq!(view.focus, Focus::Deck);
+
+        // the same cell again is refused
+        for key in [Key::Enter, Key::Enter] {
+            press(&mut game, &mut view, key);
+        }
+        assert_eq!(game.current_player(), 2);
+        assert!(press(&mut game, &mut view, Key::Char('a')));
+        assert_eq!(game.current_player(), 1);
+        assert!(!press(&mut game, &mut view, Key::Char('q')));
+    }
+
+    #[test]
+    fn board_shows_owners_bombs_and_swarm_bonus() {
+        let mut game = GameState::new([Unit::Swarm, Unit::Swarm, Unit::Titan, Unit::Warden]);
+        game.apply_move(0, (0, 0)).unwrap();
+        game.apply_move(0, (3, 4)).unwrap();
+        game.apply_move(0, (0, 2)).unwrap();
+        game.bombs[2][2] = 2;
+        let screen = render(&game, &View::default()).join("\n");
+
+        // two blue swarms back each other up
+        let swarm = effective(&game.board, game.board[0][0].as_ref().unwrap());
+        assert_eq!(swarm.top, game.board[0][0].as_ref().unwrap().top + 1);
+        assert!(screen.contains(&format!("{}{:^12}{}", BLUE, swarm.top, RESET)));
+        assert!(screen.contains(RED));
+        assert!(screen.contains(&format!("{}{:^12}{}", BOMB, "2x bomb", RESET)));
+    }
+}
@@ -1,21 +1,25 @@
 use std::io::{stdout, Write};
 
 use crate::card::*;
+use crate::error::{MoveError, PlaceError};
+use crate::game::Hint;
 
+#[macro_export]
 macro_rules! flush {
     () => {
         stdout().flush().unwrap();
     };
 }
 
+#[macro_export]
 macro_rules! input {
     ($str:expr, $msg:expr) => {
         stdin().read_line(&mut $str).expect($msg);
     };
 }
 
-pub(crate) use flush;
-pub(crate) use input;
+pub use flush;
+pub use input;
 
 // calculates score for each player
 pub fn calc_scores(board: &[[Option<Card>; 5]; 4]) -> (i8, i8) {
@@ -24,16 +28,12 @@ pub fn calc_scores(board: &[[Option<Card>; 5]; 4]) -> (i8, i8) {
 
     for i in 0..4 {
         for j in 0..5 {
-            let card = board[i][j].as_ref();
-            match card {
-                Some(c) => {
-                    if c.player == 1 {
-                        p1 += 1;
-                    } else {
-                        p2 += 1;
-                    }
+            if let Some(c) = board[i][j].as_ref() {
+                if c.player == 1 {
+                    p1 += 1;
+                } else {
+                    p2 += 1;
                 }
-                None => {}
             }
         }
     }
@@ -43,7 +43,7 @@ pub fn calc_scores(board: &[[Option<Card>; 5]; 4]) -> (i8, i8) {
 
 // ruturns a static evaluation of the game
 pub fn evaluation(board: &[[Option<Card>; 5]; 4]) -> i8 {
-    let (p1_score, p2_score) = calc_scores(&board);
+    let (p1_score, p2_score) = calc_scores(board);
     return p1_score - p2_score;
 }
 
@@ -61,7 +61,11 @@ pub fn copy_board(src: &[[Option<Card>; 5]; 4]) -> [[Option<Card>; 5]; 4] {
 }
 
 // outputs board
-pub fn show_board(board: &[[Option<Card>; 5]; 4], bombs: &[[u8; 5]; 4]) {
+pub fn show_board(
+    board: &[[Option<Card>; 5]; 4],
+    bombs: &[[u8; 5]; 4],
+    changes: &[[CellChange; 5]; 4],
+) {
     for i in 0..4 {
         for j in 0..5 {
             match &board[i][j] {
@@ -87,6 +91,11 @@ pub fn show_board(board: &[[Option<Card>; 5]; 4], bombs: &[[u8; 5]; 4]) {
                     }
                 }
             }
+            // what the last move did to the cell
+            let markers = changes[i][j].markers();
+            if !markers.is_empty() {
+                print!("<{}>", markers);
+            }
             print!("\t\t");
             flush!();
         }
@@ -94,10 +103,25 @@ pub fn show_board(board: &[[Option<Card>; 5]; 4], bombs: &[[u8; 5]; 4]) {
         println!();
         flush!();
     }
+
+    // explain the markers shown
+    let shown: Vec<String> = CellChange::LEGEND
+        .iter()
+        .filter(|(marker, _)| {
+            changes
+                .iter()
+                .flatten()
+                .any(|c| c.markers().contains(*marker))
+        })
+        .map(|(marker, meaning)| format!("<{}> {}", marker, meaning))
+        .collect();
+    if !shown.is_empty() {
+        println!("Last move: {}", shown.join(", "));
+    }
 }
 
 // outputs player deck
-pub fn show_deck(deck: &Vec<Card>, player: u8) {
+pub fn show_deck(deck: &[Card], player: u8) {
     print!("Player#{} Deck:  ", player);
     let mut i: u8 = 0;
     for c in deck.iter() {
@@ -111,44 +135,210 @@ pub fn show_deck(deck: &Vec<Card>, player: u8) {
     println!();
 }
 
-// parses single digit entered input to (card_index, y, x)
-pub fn parse_player_move(player_move: &str) -> (usize, Position) {
-    let mut cm = player_move.chars();
-    // determine card
-    let c = cm.next();
-    // determine move
-    let y = cm.next();
-    let x = cm.next();
-
-    let card: usize = match c {
-        Some(cc) => cc.to_digit(10).unwrap() as usize - 1,
-        None => {
-            panic!("Move input card index is not valid!");
+// describes a single event of a move, cells are shown as (y,x)
+pub fn describe_event(event: &Event) -> String {
+    match *event {
+        Event::PlacedCard { cell, unit, player } => format!(
+            "Player#{} placed a {:?} on ({},{})",
+            player,
+            unit,
+            cell.0 + 1,
+            cell.1 + 1
+        ),
+        Event::BombDetonated { cell, damage } => format!(
+            "Bombs on ({},{}) detonated dealing {} damage",
+            cell.0 + 1,
+            cell.1 + 1,
+            damage
+        ),
+        Event::Pulled { from, to, damage } => {
+            let mut s = format!(
+                "Card on ({},{}) was pulled to ({},{})",
+                from.0 + 1,
+                from.1 + 1,
+                to.0 + 1,
+                to.1 + 1
+            );
+            if damage > 0 {
+                s += &format!(" taking {} bomb damage", damage);
+            }
+            s
         }
-    };
+        Event::Flipped { cell } => format!("Card on ({},{}) was flipped", cell.0 + 1, cell.1 + 1),
+        Event::Battle {
+            attacker,
+            defender,
+            values,
+            result,
+        } => format!(
+            "({},{}) fought ({},{}) with {} against {}: {:?}",
+            attacker.0 + 1,
+            attacker.1 + 1,
+            defender.0 + 1,
+            defender.1 + 1,
+            values.0,
+            values.1,
+            result
+        ),
+        Event::Captured { cell, player, via } => format!(
+            "({},{}) was captured by Player#{} ({:?})",
+            cell.0 + 1,
+            cell.1 + 1,
+            player,
+            via
+        ),
+        Event::RavagerUpgraded { cell } => {
+            format!("Ravager on ({},{}) was upgraded", cell.0 + 1, cell.1 + 1)
+        }
+    }
+}
 
-    let position: Position = {
-        let mut p: Position = (99, 99);
-        match y {
-            Some(yy) => {
-                p.0 = yy.to_digit(10).unwrap() as usize - 1;
-            }
-            None => {
-                panic!("Move input y is not valid!");
-            }
+// outputs a narrated summary of what happened when a card was placed
+pub fn show_outcome(outcome: &MoveOutcome) {
+    if let Some(events) = &outcome.events {
+        println!();
+        for event in events {
+            println!("  {}", describe_event(event));
         }
+    }
 
-        match x {
-            Some(xx) => {
-                p.1 = xx.to_digit(10).unwrap() as usize - 1;
-            }
-            None => {
-                panic!("Move input x is not valid!");
+    let summary = outcome_summary(outcome);
+    if !summary.is_empty() {
+        println!("\n{}", summary);
+    }
+}
+
+// one line summary of the counters of a move, empty when nothing happened
+pub fn outcome_summary(outcome: &MoveOutcome) -> String {
+    let mut events: Vec<String> = Vec::new();
+
+    if outcome.captures > 0 {
+        events.push(format!("{} captured", outcome.captures));
+    }
+    if outcome.same_captures > 0 {
+        events.push(format!("{} by Same", outcome.same_captures));
+    }
+    if outcome.combo_captures > 0 {
+        events.push(format!("{} by Combo", outcome.combo_captures));
+    }
+    if outcome.pierce_captures > 0 {
+        events.push(format!("{} pierced", outcome.pierce_captures));
+    }
+    if outcome.pulls > 0 {
+        events.push(format!("{} pulled", outcome.pulls));
+    }
+    if outcome.flips > 0 {
+        events.push(format!("{} flipped", outcome.flips));
+    }
+    if outcome.bomb_damage > 0 {
+        events.push(format!("{} bomb damage", outcome.bomb_damage));
+    }
+
+    return events.join(", ");
+}
+
+// explains the recommended move of a hint for the player to move
+pub fn show_hint(hint: &Hint, player: u8) {
+    let (card, y, x) = hint.mov;
+    println!(
+        "\nHint: play the {:?} (card {}) on {}, {} ({}{}{})",
+        hint.unit,
+        card + 1,
+        y + 1,
+        x + 1,
+        card + 1,
+        y + 1,
+        x + 1
+    );
+
+    // immediate gains
+    let outcome = &hint.outcome;
+    println!(
+        "  It captures {} card(s) right away",
+        outcome.total_captures()
+    );
+    let mut details: Vec<String> = Vec::new();
+    if outcome.pulls > 0 {
+        details.push(format!("pulls {} card(s)", outcome.pulls));
+    }
+    if outcome.flips > 0 {
+        details.push(format!("flips {} card(s)", outcome.flips));
+    }
+    if outcome.pierce_captures > 0 {
+        details.push(format!("pierces {} card(s)", outcome.pierce_captures));
+    }
+    if outcome.bomb_damage > 0 {
+        details.push(format!("takes {} bomb damage", outcome.bomb_damage));
+    }
+    if !details.is_empty() {
+        println!("  It {}", details.join(", "));
+    }
+
+    // chains set off by the move
+    for event in outcome.events.iter().flatten() {
+        if let Event::Captured { cell, via, .. } = event {
+            if *via == CaptureKind::Same || *via == CaptureKind::Combo {
+                println!("  {:?} chain captures ({},{})", via, cell.0 + 1, cell.1 + 1);
             }
         }
+    }
 
-        p
-    };
+    // what the opponent can do about it
+    match hint.reply {
+        Some(((card, y, x), unit, _)) => println!(
+            "  The opponent's best reply is the {:?} (card {}) on {}, {}",
+            unit,
+            card + 1,
+            y + 1,
+            x + 1
+        ),
+        None => println!("  It is the last move of the game"),
+    }
+
+    let swing = hint.swing(player);
+    let mut expected = format!("  Expected score swing: {:+} card(s)", swing);
+    if (player == 1 && hint.score > 50) || (player == 2 && hint.score < -50) {
+        expected += ", it wins the game";
+    } else if (player == 1 && hint.score < -50) || (player == 2 && hint.score > 50) {
+        expected += ", the game is lost anyway";
+    }
+    println!("{}", expected);
+}
+
+// parses single digit entered input to (card_index, y, x)
+pub fn parse_player_move(player_move: &str) -> Result<(usize, Position), MoveError> {
+    // input must be exactly 3 digits, all of them 1 based
+    let digits: Vec<usize> = player_move
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as usize))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or(MoveError::BadFormat)?;
+    if digits.len() != 3 || digits.contains(&0) {
+        return Err(MoveError::BadFormat);
+    }
+
+    // determine card
+    let card = digits[0] - 1;
+    // determine move
+    let position: Position = (digits[1] - 1, digits[2] - 1);
+    if position.0 >= 4 || position.1 >= 5 {
+        return Err(PlaceError::CellOutOfBounds(position).into());
+    }
+
+    return Ok((card, position));
+}
+
+// parses the deck unit types (player1 player1 player2 player2)
+pub fn parse_decks(deck_types: &str) -> Option<[Unit; 4]> {
+    let digits: Vec<&str> = deck_types.split_whitespace().collect();
+    if digits.len() != 4 {
+        return None;
+    }
+
+    let mut units = [Unit::Warden; 4];
+    for i in 0..4 {
+        units[i] = Unit::from_digit(digits[i].parse().ok()?)?;
+    }
 
-    return (card, position);
+    return Some(units);
 }
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use legions_ai::ai::SearchOptions;
+use legions_ai::card::Unit;
+use legions_ai::game::GameState;
+
+// plays the first available moves until only the given number of cards are left
+fn position(cards_left: usize) -> GameState {
+    let mut game = GameState::new([Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Saboteur]);
+    while game.deck1.len() + game.deck2.len() > cards_left {
+        let (card, y, x) = game.perft(1)[0].0;
+        game.apply_move(card, (y, x)).unwrap();
+    }
+    return game;
+}
+
+#[test]
+fn hint_explains_the_best_move() {
+    let options = SearchOptions {
+        depth: Some((2, 1)),
+        ..Default::default()
+    };
+
+    for cards_left in [1, 6, 9] {
+        let mut game = position(cards_left);
+        let board = format!("{:?}", game.board);
+        let hint = game.hint(&options);
+
+        // the position is left untouched
+        assert_eq!(format!("{:?}", game.board), board);
+        assert_eq!(game.deck1.len() + game.deck2.len(), cards_left);
+
+        let best = game.best_move(&options);
+        let (card, y, x, _) = best.best;
+        assert_eq!((hint.mov, hint.score), ((card, y, x), best.score));
+
+        let outcome = game.apply_move(card, (y, x)).unwrap();
+        assert_eq!(hint.outcome, outcome);
+        match hint.reply {
+            Some((mov, _, score)) => {
+                let reply = game.best_move(&options);
+                let (card, y, x, _) = reply.best;
+                assert_eq!((mov, score), ((card, y, x), reply.score));
+            }
+            None => assert!(game.is_over()),
+        }
+    }
+}
+
+#[test]
+fn blunders_are_measured_against_the_best_move() {
+    let options = SearchOptions {
+        depth: Some((2, 1)),
+        ..Default::default()
+    };
+    let mut game = position(8);
+    let player = game.current_player();
+    let result = game.best_move(&options);
+
+    let (card, y, x, _) = result.best;
+    assert!(game
+        .check_blunder(card, (y, x), &options)
+        .unwrap()
+        .is_none());
+
+    for ((card, y, x), score) in result.scores {
+        match game.check_blunder(card, (y, x), &options).unwrap() {
+            Some(blunder) => {
+                assert_eq!(blunder.score, score);
+                assert_eq!(blunder.best_score, result.score);
+                assert!(blunder.loss(player) > 0);
+            }
+            None => assert_eq!(score, result.score),
+        }
+    }
+
+    // moves that can't be played are refused before searching
+    assert!(game.check_blunder(20, (0, 0), &options).is_err());
+}
+
+#[test]
+fn moves_cut_off_by_the_time_limit_are_still_checked() {
+    // one ply deep there is no shallower search to fall back on, so only the first root move is
+    // finished
+    let options = SearchOptions {
+        depth: Some((1, 0)),
+        ..Default::default()
+    };
+    let timed = SearchOptions {
+        time: Some(Duration::from_nanos(1)),
+        ..options.clone()
+    };
+    let mut game = position(8);
+    let player = game.current_player();
+    let result = game.best_move(&options);
+    assert!(game.best_move(&timed).scores.len() < result.scores.len());
+
+    // the worst move is measured on its own
+    let &((card, y, x), worst) = result
+        .scores
+        .iter()
+        .min_by_key(|&&(_, score)| if player == 1 { score } else { -score })
+        .unwrap();
+    assert_ne!(worst, result.score);
+    let blunder = game
+        .check_blunder(card, (y, x), &timed)
+        .unwrap()
+        .expect("the worst move is not a blunder");
+    assert_eq!(blunder.score, worst);
+    assert!(blunder.loss(player) > 0);
+
+    for ((card, y, x), score) in result.scores {
+        if let Some(blunder) = game.check_blunder(card, (y, x), &timed).unwrap() {
+            assert_eq!(blunder.score, score);
+        }
+    }
+}
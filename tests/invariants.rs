@@ -0,0 +1,155 @@
+use std::env;
+
+use legions_ai::card::*;
+use legions_ai::rng::Rng;
+use legions_ai::utility::calc_scores;
+
+// number of random games, can be raised with LEGIONS_SEEDS for longer fuzzing runs
+fn seeds() -> u64 {
+    return env::var("LEGIONS_SEEDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300);
+}
+
+fn random_unit(rng: &mut Rng) -> Unit {
+    return Unit::from_digit(rng.below(9) as u32 + 1).unwrap();
+}
+
+// checks every invariant of the rules engine, panicking with the seed and ply on failure
+fn check_invariants(
+    board: &[[Option<Card>; 5]; 4],
+    bombs: &[[u8; 5]; 4],
+    deck1: &[Card],
+    deck2: &[Card],
+    seed: u64,
+    ply: usize,
+) {
+    let mut placed = 0;
+    let cards = board
+        .iter()
+        .flatten()
+        .flatten()
+        .chain(deck1.iter())
+        .chain(deck2.iter());
+    for card in cards {
+        for value in [card.top, card.right, card.bottom, card.left] {
+            assert!(
+                (1..=10).contains(&value),
+                "seed {} ply {}: stat {} out of range in {:?}",
+                seed,
+                ply,
+                value,
+                card
+            );
+        }
+        assert!(
+            card.player == 1 || card.player == 2,
+            "seed {} ply {}: invalid owner in {:?}",
+            seed,
+            ply,
+            card
+        );
+    }
+
+    for i in 0..4 {
+        for j in 0..5 {
+            if board[i][j].is_some() {
+                placed += 1;
+                assert_eq!(
+                    bombs[i][j], 0,
+                    "seed {} ply {}: bombs left under the card on ({},{})",
+                    seed, ply, i, j
+                );
+            }
+        }
+    }
+
+    // every card is either on the board or in a deck
+    assert_eq!(
+        placed + deck1.len() + deck2.len(),
+        16,
+        "seed {} ply {}: cards were lost or duplicated",
+        seed,
+        ply
+    );
+    assert_eq!(
+        placed,
+        ply + 1,
+        "seed {} ply {}: wrong card count",
+        seed,
+        ply
+    );
+
+    // player 1 gets a bonus point
+    let (p1, p2) = calc_scores(board);
+    assert_eq!(
+        (p1 + p2) as usize,
+        placed + 1,
+        "seed {} ply {}: scores don't add up",
+        seed,
+        ply
+    );
+}
+
+// plays a random legal game checking the invariants after every placement
+fn random_game(seed: u64) {
+    let mut rng = Rng::new(seed);
+
+    let mut deck1: Vec<Card> = Vec::new();
+    let mut deck2: Vec<Card> = Vec::new();
+    Card::add_to_deck(&mut deck1, random_unit(&mut rng), 1);
+    Card::add_to_deck(&mut deck1, random_unit(&mut rng), 1);
+    Card::add_to_deck(&mut deck2, random_unit(&mut rng), 2);
+    Card::add_to_deck(&mut deck2, random_unit(&mut rng), 2);
+
+    let mut board: [[Option<Card>; 5]; 4] = Default::default();
+    let mut bombs: [[u8; 5]; 4] = [[0; 5]; 4];
+
+    for ply in 0..16 {
+        let player = (ply % 2) as u8 + 1;
+        let deck_len = if player == 1 {
+            deck1.len()
+        } else {
+            deck2.len()
+        };
+
+        // pick a random card and a random empty cell
+        let card = rng.below(deck_len);
+        let empty: Vec<Position> = (0..4)
+            .flat_map(|i| (0..5).map(move |j| (i, j)))
+            .filter(|&(i, j)| board[i][j].is_none())
+            .collect();
+        let (y, x) = empty[rng.below(empty.len())];
+
+        let unit = if player == 1 {
+            deck1[card].name
+        } else {
+            deck2[card].name
+        };
+        let mut neighbours = Card::get_neighbours(&board, y, x, unit);
+
+        Card::place_card(
+            &mut board,
+            &mut deck1,
+            &mut deck2,
+            card,
+            (y, x),
+            player,
+            &mut bombs,
+            &mut neighbours,
+        )
+        .unwrap_or_else(|e| panic!("seed {} ply {}: legal move refused: {}", seed, ply, e));
+
+        check_invariants(&board, &bombs, &deck1, &deck2, seed, ply);
+    }
+
+    assert!(deck1.is_empty() && deck2.is_empty());
+}
+
+#[test]
+fn invariants_hold_for_random_games() {
+    for seed in 0..seeds() {
+        random_game(seed);
+    }
+}
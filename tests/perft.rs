@@ -0,0 +1,104 @@
+use legions_ai::card::Unit;
+use legions_ai::game::GameState;
+
+const UNITS: [Unit; 9] = [
+    Unit::Warden,
+    Unit::Keeper,
+    Unit::Siren,
+    Unit::Saboteur,
+    Unit::Ravager,
+    Unit::Titan,
+    Unit::Slayer,
+    Unit::Swarm,
+    Unit::Lancer,
+];
+
+fn total(game: &mut GameState, depth: u8) -> u64 {
+    let divided: u64 = game.perft(depth).iter().map(|(_, count)| count).sum();
+    let total = game.perft_total(depth);
+    if depth > 0 && !game.is_over() {
+        assert_eq!(divided, total);
+    }
+    return total;
+}
+
+// plays the first available move until only the given number of cards are left
+fn position(units: [Unit; 4], cards_left: usize) -> GameState {
+    let mut game = GameState::new(units);
+    while game.deck1.len() + game.deck2.len() > cards_left {
+        let (card, y, x) = game.perft(1)[0].0;
+        game.apply_move(card, (y, x)).unwrap();
+    }
+    return game;
+}
+
+#[test]
+fn start_position_counts() {
+    for i in 0..9 {
+        let units = [
+            UNITS[i],
+            UNITS[(i + 1) % 9],
+            UNITS[(i + 2) % 9],
+            UNITS[(i + 3) % 9],
+        ];
+        let mut game = GameState::new(units);
+
+        assert_eq!(total(&mut game, 0), 1, "{:?}", units);
+        assert!(game.perft(0).is_empty());
+        assert_eq!(total(&mut game, 1), 160, "{:?}", units);
+        assert_eq!(total(&mut game, 2), 24_320, "{:?}", units);
+    }
+}
+
+#[test]
+fn identical_cards_are_all_counted() {
+    // the search plays only one of identical cards, perft counts the raw moves
+    let mut game = GameState::new([Unit::Titan, Unit::Titan, Unit::Swarm, Unit::Slayer]);
+    assert_eq!(total(&mut game, 1), 8 * 20);
+    assert_eq!(total(&mut game, 2), 8 * 20 * 8 * 19);
+
+    let mut game = GameState::new([Unit::Lancer, Unit::Warden, Unit::Lancer, Unit::Siren]);
+    assert_eq!(total(&mut game, 1), 8 * 20);
+    assert_eq!(total(&mut game, 2), 8 * 20 * 8 * 19);
+}
+
+#[test]
+fn midgame_counts() {
+    let mut game = position([Unit::Siren, Unit::Saboteur, Unit::Titan, Unit::Lancer], 8);
+
+    // 4 cards each on 12 empty cells
+    assert_eq!(total(&mut game, 1), 48);
+    assert_eq!(total(&mut game, 2), 48 * 44);
+    assert_eq!(total(&mut game, 3), 48 * 44 * 30);
+}
+
+#[test]
+fn finished_games_are_counted_once() {
+    let mut game = position([Unit::Keeper, Unit::Ravager, Unit::Slayer, Unit::Swarm], 3);
+
+    // 2 cards of player 2 and 1 card of player 1 on 7 empty cells, the board is never filled
+    let expected = (2 * 7) * (1 * 6) * (1 * 5);
+    assert_eq!(total(&mut game, 3), expected);
+    assert_eq!(total(&mut game, 10), expected);
+}
+
+#[test]
+fn divide_matches_applied_moves() {
+    let mut game = position(
+        [Unit::Warden, Unit::Lancer, Unit::Saboteur, Unit::Siren],
+        10,
+    );
+    let board = format!("{:?}", game.board);
+    let bombs = game.bombs;
+
+    for ((card, y, x), count) in game.perft(3) {
+        game.apply_move(card, (y, x)).unwrap();
+        assert_eq!(total(&mut game, 2), count, "move {:?}", (card, y, x));
+        assert!(game.undo());
+    }
+
+    // counting leaves the position untouched
+    assert_eq!(format!("{:?}", game.board), board);
+    assert_eq!(game.bombs, bombs);
+    assert_eq!(game.deck1.len() + game.deck2.len(), 10);
+}
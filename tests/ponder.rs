@@ -0,0 +1,122 @@
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use legions_ai::ai::SearchOptions;
+use legions_ai::book::OpeningBook;
+use legions_ai::card::Unit;
+use legions_ai::game::GameState;
+use legions_ai::tt::TranspositionTable;
+
+const UNITS: [Unit; 4] = [Unit::Siren, Unit::Titan, Unit::Saboteur, Unit::Swarm];
+
+// plays the first available moves until only the given number of cards are left
+fn position(cards_left: usize) -> GameState {
+    let mut game = GameState::new(UNITS);
+    while game.deck1.len() + game.deck2.len() > cards_left {
+        let (card, y, x) = game.perft(1)[0].0;
+        game.apply_move(card, (y, x)).unwrap();
+    }
+    return game;
+}
+
+fn options() -> SearchOptions {
+    return SearchOptions {
+        depth: Some((2, 1)),
+        ..Default::default()
+    };
+}
+
+// best move of a game that never pondered, after the same moves
+fn fresh_scores(
+    cards_left: usize,
+    reply: (usize, usize, usize),
+) -> (i8, Vec<((usize, usize, usize), i8)>) {
+    let mut game = position(cards_left);
+    game.apply_move(reply.0, (reply.1, reply.2)).unwrap();
+    let result = game.best_move(&options());
+
+    let mut scores = result.scores;
+    scores.sort();
+    return (result.score, scores);
+}
+
+fn wait_for_ponder(game: &GameState) {
+    let start = Instant::now();
+    while game.is_pondering() {
+        assert!(
+            start.elapsed() < Duration::from_secs(120),
+            "pondering never ended"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn pondered_search_matches_a_fresh_one() {
+    let mut game = position(4);
+    game.ponder(&options());
+    wait_for_ponder(&game);
+
+    for reply in [game.perft(1)[0].0, game.perft(1)[5].0] {
+        game.apply_move(reply.0, (reply.1, reply.2)).unwrap();
+        let result = game.best_move(&options());
+        assert!(game.undo());
+
+        let mut scores = result.scores;
+        scores.sort();
+        assert_eq!((result.score, scores), fresh_scores(4, reply));
+    }
+}
+
+#[test]
+fn stopped_ponder_leaves_the_search_intact() {
+    let mut game = position(10);
+    let reply = game.perft(1)[3].0;
+
+    // the opponent answers right away, cutting pondering short
+    game.ponder(&options());
+    thread::sleep(Duration::from_millis(20));
+    game.apply_move(reply.0, (reply.1, reply.2)).unwrap();
+    let result = game.best_move(&options());
+    assert!(!game.is_pondering());
+
+    let mut scores = result.scores;
+    scores.sort();
+    assert_eq!((result.score, scores), fresh_scores(10, reply));
+}
+
+#[test]
+fn book_search_is_not_reused_without_the_book() {
+    let mut game = position(4);
+    let reply = game.perft(1)[0].0;
+
+    // the book holds a move for the position after the reply
+    game.apply_move(reply.0, (reply.1, reply.2)).unwrap();
+    let key = TranspositionTable::hash(
+        &game.board,
+        &game.bombs,
+        &game.deck1,
+        &game.deck2,
+        game.current_player(),
+    );
+    let mut book = OpeningBook::default();
+    book.insert(key, game.perft(1)[0].0, 42);
+    assert!(game.undo());
+
+    let with_book = SearchOptions {
+        book: Some(Arc::new(book)),
+        ..options()
+    };
+    game.ponder(&with_book);
+    wait_for_ponder(&game);
+
+    // a search without the book scores every move
+    game.apply_move(reply.0, (reply.1, reply.2)).unwrap();
+    let result = game.best_move(&options());
+    let mut scores = result.scores;
+    scores.sort();
+    assert_eq!((result.score, scores), fresh_scores(4, reply));
+}
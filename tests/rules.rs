@@ -0,0 +1,597 @@
+use legions_ai::card::*;
+use legions_ai::utility::calc_scores;
+
+// builds a card with the given (top, right, bottom, left) values
+fn card(name: Unit, values: [u8; 4], player: u8) -> Card {
+    return Card {
+        name,
+        top: values[0],
+        right: values[1],
+        bottom: values[2],
+        left: values[3],
+        player,
+    };
+}
+
+fn empty() -> ([[Option<Card>; 5]; 4], [[u8; 5]; 4]) {
+    return (Default::default(), [[0; 5]; 4]);
+}
+
+// places the card on the board the same way a player would
+fn place(
+    board: &mut [[Option<Card>; 5]; 4],
+    bombs: &mut [[u8; 5]; 4],
+    card: Card,
+    position: Position,
+) -> MoveOutcome {
+    let player = card.player;
+    let mut neighbours = Card::get_neighbours(board, position.0, position.1, card.name);
+    let mut deck1: Vec<Card> = Vec::new();
+    let mut deck2: Vec<Card> = Vec::new();
+    if player == 1 {
+        deck1.push(card);
+    } else {
+        deck2.push(card);
+    }
+
+    return Card::place_card_narrated(
+        board,
+        &mut deck1,
+        &mut deck2,
+        0,
+        position,
+        player,
+        bombs,
+        &mut neighbours,
+    )
+    .unwrap();
+}
+
+fn at(board: &[[Option<Card>; 5]; 4], y: usize, x: usize) -> &Card {
+    return board[y][x].as_ref().unwrap();
+}
+
+fn values(card: &Card) -> [u8; 4] {
+    return [card.top, card.right, card.bottom, card.left];
+}
+
+#[test]
+fn higher_value_captures() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [5, 5, 3, 5], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Warden, [4, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(outcome.captures, 1);
+    assert_eq!(outcome.total_captures(), 1);
+    assert_eq!(calc_scores(&board), (3, 0));
+}
+
+#[test]
+fn lower_or_equal_value_does_not_capture() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [5, 5, 4, 5], 2));
+    board[1][2] = Some(card(Unit::Titan, [5, 5, 5, 6], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Warden, [4, 5, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 2);
+    assert_eq!(at(&board, 1, 2).player, 2);
+    assert_eq!(outcome.total_captures(), 0);
+}
+
+#[test]
+fn single_tie_is_not_same() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [5, 5, 4, 5], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Warden, [4, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 2);
+    assert_eq!(outcome.same_captures, 0);
+}
+
+#[test]
+fn same_captures_tied_neighbours_and_chains_into_combo() {
+    let (mut board, mut bombs) = empty();
+    // ties with the placed card on top and left
+    board[0][1] = Some(card(Unit::Titan, [1, 9, 4, 1], 2));
+    board[1][0] = Some(card(Unit::Titan, [1, 4, 1, 1], 2));
+    // beaten by the top card once it is captured through Same
+    board[0][2] = Some(card(Unit::Titan, [1, 1, 1, 2], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Warden, [4, 1, 1, 4], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(at(&board, 1, 0).player, 1);
+    assert_eq!(at(&board, 0, 2).player, 1);
+    assert_eq!(outcome.same_captures, 2);
+    assert_eq!(outcome.combo_captures, 1);
+
+    // the combo capture happens right after the Same capture that caused it
+    let captures: Vec<(Position, CaptureKind)> = outcome
+        .events
+        .unwrap()
+        .iter()
+        .filter_map(|e| match *e {
+            Event::Captured { cell, via, .. } => Some((cell, via)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        captures,
+        vec![
+            ((0, 1), CaptureKind::Same),
+            ((0, 2), CaptureKind::Combo),
+            ((1, 0), CaptureKind::Same),
+        ]
+    );
+}
+
+#[test]
+fn tie_with_own_card_counts_towards_same() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 4, 1], 1));
+    board[1][0] = Some(card(Unit::Titan, [1, 4, 1, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Warden, [4, 1, 1, 4], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 1, 0).player, 1);
+    assert_eq!(outcome.same_captures, 1);
+}
+
+#[test]
+fn warden_defense_bonus_only_against_enemies() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Warden, [1, 1, 5, 1], 2));
+    board[1][0] = Some(card(Unit::Warden, [1, 5, 1, 1], 1));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [5, 1, 1, 5], 1),
+        (1, 1),
+    );
+
+    // enemy Warden defends with 6 and is not captured
+    assert_eq!(at(&board, 0, 1).player, 2);
+    let battles: Vec<((u8, u8), FightResult)> = outcome
+        .events
+        .unwrap()
+        .iter()
+        .filter_map(|e| match *e {
+            Event::Battle { values, result, .. } => Some((values, result)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        battles,
+        vec![((5, 6), FightResult::Lose), ((5, 5), FightResult::Tie)]
+    );
+}
+
+#[test]
+fn warden_is_captured_by_a_value_above_its_bonus() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Warden, [1, 1, 5, 1], 2));
+
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [7, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 1);
+}
+
+#[test]
+fn slayer_uses_swapped_values() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 5, 1], 2));
+    board[1][2] = Some(card(Unit::Titan, [1, 1, 1, 5], 2));
+
+    // weak top wins against the strong bottom, strong right loses against the weak left
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Slayer, [1, 9, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(at(&board, 1, 2).player, 2);
+    assert_eq!(outcome.captures, 1);
+}
+
+#[test]
+fn swarm_attacks_with_ally_count_bonus() {
+    let (mut board, mut bombs) = empty();
+    board[3][3] = Some(card(Unit::Swarm, [7, 3, 3, 3], 1));
+    board[3][4] = Some(card(Unit::Swarm, [7, 3, 3, 3], 1));
+    // enemy swarms do not help
+    board[2][0] = Some(card(Unit::Swarm, [7, 3, 3, 3], 2));
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 4, 1], 2));
+
+    // 3 + 2 allies beats 4
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Swarm, [3, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(outcome.captures, 1);
+    assert_eq!(Card::swarm_count(&board, 1), 2);
+    assert_eq!(Card::swarm_count(&board, 2), 0);
+}
+
+#[test]
+fn lone_swarm_gets_no_bonus() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 4, 1], 2));
+
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Swarm, [3, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 2);
+}
+
+#[test]
+fn swarm_defends_with_ally_count_bonus() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Swarm, [3, 3, 3, 3], 2));
+    board[3][4] = Some(card(Unit::Swarm, [3, 3, 3, 3], 2));
+
+    // 4 against 3 + 1 ally is a tie
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [4, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    assert_eq!(at(&board, 0, 1).player, 2);
+    assert!(outcome.events.unwrap().contains(&Event::Battle {
+        attacker: (1, 1),
+        defender: (0, 1),
+        values: (4, 4),
+        result: FightResult::Tie,
+    }));
+}
+
+#[test]
+fn ravager_upgrades_when_capturing() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 3, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Ravager, [8, 4, 6, 2], 1),
+        (1, 1),
+    );
+
+    assert_eq!(values(at(&board, 1, 1)), [9, 5, 7, 3]);
+    assert!(outcome
+        .events
+        .unwrap()
+        .contains(&Event::RavagerUpgraded { cell: (1, 1) }));
+}
+
+#[test]
+fn ravager_upgrades_when_captured() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Ravager, [8, 4, 2, 10], 2));
+
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [5, 1, 1, 1], 1),
+        (1, 1),
+    );
+
+    let ravager = at(&board, 0, 1);
+    assert_eq!(ravager.player, 1);
+    // upgrades are capped at 10
+    assert_eq!(values(ravager), [9, 5, 3, 10]);
+}
+
+#[test]
+fn lancer_pierces_the_card_behind_a_captured_card() {
+    let (mut board, mut bombs) = empty();
+    board[1][1] = Some(card(Unit::Titan, [1, 1, 3, 1], 2));
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 5, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Lancer, [6, 4, 6, 4], 1),
+        (2, 1),
+    );
+
+    assert_eq!(at(&board, 1, 1).player, 1);
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(outcome.captures, 1);
+    assert_eq!(outcome.pierce_captures, 1);
+    // cards are back in their original positions after the pierce
+    assert_eq!(at(&board, 2, 1).name, Unit::Lancer);
+    assert_eq!(values(at(&board, 1, 1)), [1, 1, 3, 1]);
+    assert_eq!(values(at(&board, 0, 1)), [1, 1, 5, 1]);
+}
+
+#[test]
+fn lancer_does_not_pierce_without_a_capture() {
+    let (mut board, mut bombs) = empty();
+    board[1][1] = Some(card(Unit::Titan, [1, 1, 7, 1], 2));
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Lancer, [6, 4, 6, 4], 1),
+        (2, 1),
+    );
+
+    assert_eq!(at(&board, 1, 1).player, 2);
+    assert_eq!(at(&board, 0, 1).player, 2);
+    assert_eq!(outcome.total_captures(), 0);
+}
+
+#[test]
+fn lancer_pierce_is_a_single_step() {
+    let (mut board, mut bombs) = empty();
+    board[2][1] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+    board[1][1] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Lancer, [6, 4, 6, 4], 1),
+        (3, 1),
+    );
+
+    assert_eq!(at(&board, 2, 1).player, 1);
+    assert_eq!(at(&board, 1, 1).player, 1);
+    assert_eq!(at(&board, 0, 1).player, 2);
+    assert_eq!(outcome.pierce_captures, 1);
+}
+
+#[test]
+fn siren_pulls_distant_cards_through_bombs() {
+    let (mut board, mut bombs) = empty();
+    board[0][0] = Some(card(Unit::Titan, [5, 5, 5, 5], 2));
+    bombs[1][0] = 1;
+    bombs[2][0] = 2;
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Siren, [7, 4, 4, 5], 1),
+        (3, 0),
+    );
+
+    assert!(board[0][0].is_none());
+    // the pulled card takes the damage of every bomb on its way
+    let pulled = at(&board, 2, 0);
+    assert_eq!(values(pulled), [2, 2, 2, 2]);
+    assert_eq!(pulled.player, 1);
+    assert_eq!(bombs, [[0; 5]; 4]);
+    assert_eq!(outcome.pulls, 1);
+    assert_eq!(outcome.bomb_damage, 3);
+    assert_eq!(outcome.captures, 1);
+}
+
+#[test]
+fn changes_follow_the_pulled_card() {
+    let (mut board, mut bombs) = empty();
+    board[0][0] = Some(card(Unit::Titan, [5, 5, 5, 5], 2));
+    board[1][3] = Some(card(Unit::Warden, [1, 5, 9, 5], 2));
+    bombs[1][0] = 1;
+
+    let changes = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Siren, [7, 4, 4, 5], 1),
+        (2, 0),
+    )
+    .changes();
+
+    assert_eq!(changes[2][0].markers(), "+");
+    assert_eq!(changes[1][0].markers(), "CMD");
+    assert_eq!(changes[0][0], CellChange::default());
+    assert_eq!(changes[1][3], CellChange::default());
+
+    let changes = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Titan, [7, 4, 6, 3], 1),
+        (2, 3),
+    )
+    .changes();
+    assert_eq!(changes[1][3].markers(), "CF");
+}
+
+#[test]
+fn pulled_card_stats_stay_at_least_one() {
+    let (mut board, mut bombs) = empty();
+    board[1][4] = Some(card(Unit::Titan, [2, 2, 2, 2], 2));
+    bombs[1][2] = 3;
+    bombs[1][3] = 3;
+
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Siren, [7, 4, 4, 5], 1),
+        (1, 1),
+    );
+
+    assert_eq!(values(at(&board, 1, 2)), [1, 1, 1, 1]);
+    assert_eq!(at(&board, 1, 2).player, 1);
+}
+
+#[test]
+fn siren_leaves_adjacent_cards_in_place() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [9, 9, 9, 9], 2));
+    board[1][4] = Some(card(Unit::Titan, [9, 9, 9, 9], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Siren, [7, 4, 4, 5], 1),
+        (1, 1),
+    );
+
+    assert!(board[0][1].is_some());
+    assert!(board[1][4].is_none());
+    assert!(board[1][2].is_some());
+    assert_eq!(outcome.pulls, 1);
+}
+
+#[test]
+fn keeper_attacks_the_first_card_in_each_direction() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [1, 1, 2, 1], 2));
+    board[3][4] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Keeper, [9, 5, 1, 5], 1),
+        (3, 1),
+    );
+
+    // the distant card is captured but not moved
+    assert_eq!(at(&board, 0, 1).player, 1);
+    assert_eq!(at(&board, 3, 4).player, 1);
+    assert_eq!(outcome.captures, 2);
+    assert_eq!(outcome.pulls, 0);
+}
+
+#[test]
+fn titan_flips_neighbours_before_battling() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Warden, [1, 5, 9, 5], 2));
+    board[1][2] = Some(card(Unit::Warden, [5, 1, 5, 9], 2));
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Titan, [7, 4, 6, 3], 1),
+        (1, 1),
+    );
+
+    let top = at(&board, 0, 1);
+    assert_eq!(values(top), [9, 5, 1, 5]);
+    assert_eq!(top.player, 1);
+    let right = at(&board, 1, 2);
+    assert_eq!(values(right), [5, 9, 5, 1]);
+    assert_eq!(right.player, 1);
+    assert_eq!(outcome.flips, 2);
+}
+
+#[test]
+fn saboteur_bombs_only_empty_neighbours() {
+    let (mut board, mut bombs) = empty();
+    board[0][1] = Some(card(Unit::Titan, [9, 9, 9, 9], 2));
+
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [6, 5, 4, 5], 1),
+        (1, 1),
+    );
+
+    let mut expected = [[0; 5]; 4];
+    expected[1][0] = 1;
+    expected[1][2] = 1;
+    expected[2][1] = 1;
+    assert_eq!(bombs, expected);
+}
+
+#[test]
+fn bombs_detonate_on_the_placed_card() {
+    let (mut board, mut bombs) = empty();
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [6, 5, 4, 5], 1),
+        (1, 1),
+    );
+    place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Saboteur, [6, 5, 4, 5], 2),
+        (1, 3),
+    );
+    assert_eq!(bombs[1][2], 2);
+
+    let outcome = place(
+        &mut board,
+        &mut bombs,
+        card(Unit::Titan, [7, 4, 6, 2], 1),
+        (1, 2),
+    );
+
+    // stats go down to a minimum of 1 before the battles
+    assert_eq!(values(at(&board, 1, 2)), [5, 2, 4, 1]);
+    assert_eq!(bombs[1][2], 0);
+    assert_eq!(outcome.bomb_damage, 2);
+    assert_eq!(at(&board, 1, 3).player, 2);
+}
+
+#[test]
+fn placing_on_an_occupied_cell_is_refused() {
+    let (mut board, mut bombs) = empty();
+    board[0][0] = Some(card(Unit::Titan, [1, 1, 1, 1], 2));
+    let mut deck1 = vec![card(Unit::Warden, [6, 6, 4, 4], 1)];
+    let mut deck2: Vec<Card> = Vec::new();
+    let mut neighbours = Card::get_neighbours(&board, 0, 0, Unit::Warden);
+
+    let result = Card::place_card(
+        &mut board,
+        &mut deck1,
+        &mut deck2,
+        0,
+        (0, 0),
+        1,
+        &mut bombs,
+        &mut neighbours,
+    );
+
+    assert!(result.is_err());
+    assert_eq!(deck1.len(), 1);
+}
@@ -0,0 +1,195 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use legions_ai::card::Unit;
+use legions_ai::game::GameState;
+use legions_ai::json::Value;
+use legions_ai::server;
+
+// starts a server on a free local port
+fn start() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || server::serve(listener));
+    return address;
+}
+
+// sends the raw request and returns the status code and the JSON body of the response
+fn send(address: SocketAddr, raw: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(raw.as_bytes()).unwrap();
+    return receive(stream);
+}
+
+// reads the whole response of the server
+fn receive(mut stream: TcpStream) -> (u16, Value) {
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    return (status, Value::parse(body).unwrap());
+}
+
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    return send(
+        address,
+        &format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        ),
+    );
+}
+
+// the number of cards left in both decks of a state
+fn cards_left(state: &Value) -> usize {
+    return ["deck1", "deck2"]
+        .iter()
+        .map(|deck| state.get(deck).and_then(|d| d.as_array()).unwrap().len())
+        .sum();
+}
+
+#[test]
+fn games_are_played_over_http() {
+    let address = start();
+
+    let (status, response) = request(address, "POST", "/games", "{\"decks\":[6,3,8,1]}");
+    assert_eq!(status, 200);
+    let id = response.get("id").and_then(|i| i.as_u64()).unwrap();
+    let game = format!("/games/{}", id);
+
+    // play the first legal moves, followed on a local game, until a few cards are left so the
+    // search is short
+    let mut local = GameState::new([Unit::Titan, Unit::Siren, Unit::Swarm, Unit::Warden]);
+    let mut state = response.get("state").unwrap().clone();
+    for _ in 0..12 {
+        let (card, y, x) = local.perft(1)[0].0;
+        local.apply_move(card, (y, x)).unwrap();
+        let body = format!("{{\"card\":{},\"row\":{},\"col\":{}}}", card, y, x);
+        let (status, response) = request(address, "POST", &format!("{}/moves", game), &body);
+        assert_eq!(status, 200, "{}", response);
+        state = response.get("state").unwrap().clone();
+    }
+    let left = cards_left(&state);
+    assert_eq!(left, local.deck1.len() + local.deck2.len());
+
+    let (status, response) = request(
+        address,
+        "POST",
+        &format!("{}/best", game),
+        "{\"apply\":true}",
+    );
+    assert_eq!(status, 200, "{}", response);
+    assert!(response.get("move").is_some());
+    assert_eq!(cards_left(response.get("state").unwrap()), left - 1);
+
+    // the AI move is taken back
+    let (status, response) = request(address, "POST", &format!("{}/undo", game), "");
+    assert_eq!(status, 200, "{}", response);
+    let (status, response) = request(address, "GET", &game, "");
+    assert_eq!(status, 200);
+    assert_eq!(cards_left(response.get("state").unwrap()), left);
+
+    // an illegal move is refused without ending the game
+    let body = "{\"card\":9,\"row\":0,\"col\":0}";
+    let (status, _) = request(address, "POST", &format!("{}/moves", game), body);
+    assert_eq!(status, 400);
+    assert_eq!(request(address, "GET", &game, "").0, 200);
+    assert_eq!(request(address, "GET", "/games/999", "").0, 404);
+}
+
+#[test]
+fn malformed_requests_are_refused() {
+    let address = start();
+
+    assert_eq!(send(address, "garbage\r\n\r\n").0, 400);
+    assert_eq!(request(address, "POST", "/games", "{\"decks\":").0, 400);
+    assert_eq!(request(address, "POST", "/nowhere", "").0, 404);
+
+    // nesting that would overflow a recursive parser
+    let nested = format!("{{\"decks\":{}}}", "[".repeat(10_000));
+    assert_eq!(request(address, "POST", "/games", &nested).0, 400);
+
+    // a huge body is refused before it is read
+    let (status, response) = send(
+        address,
+        "POST /games HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n",
+    );
+    assert_eq!(status, 413, "{}", response);
+
+    // the server is still up
+    assert_eq!(
+        request(address, "POST", "/games", "{\"decks\":[1,2,3,4]}").0,
+        200
+    );
+}
+
+#[test]
+fn slow_clients_are_cut_off() {
+    let address = start();
+    let stream = TcpStream::connect(address).unwrap();
+
+    // a byte at a time never finishes the request
+    let mut writer = stream.try_clone().unwrap();
+    thread::spawn(move || {
+        for _ in 0..100 {
+            if writer.write_all(b"G").is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    });
+
+    let start = Instant::now();
+    assert_eq!(receive(stream).0, 408);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn connections_are_limited() {
+    let address = start();
+
+    // idle clients take every connection
+    let idle: Vec<TcpStream> = (0..16)
+        .map(|_| TcpStream::connect(address).unwrap())
+        .collect();
+    let (status, response) = receive(TcpStream::connect(address).unwrap());
+    assert_eq!(status, 503, "{}", response);
+
+    // their connections are freed once they leave
+    drop(idle);
+    let start = Instant::now();
+    while request(address, "POST", "/games", "{\"decks\":[1,2,3,4]}").0 != 200 {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "connections never freed"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn analysis_reads_the_query_string() {
+    let address = start();
+    let (_, response) = request(address, "POST", "/games", "{\"decks\":[1,2,3,4]}");
+    let id = response.get("id").and_then(|i| i.as_u64()).unwrap();
+
+    // the whole opening can't be searched in a millisecond
+    let path = format!("/games/{}/analysis?seed=3&time_ms=1", id);
+    let (status, response) = request(address, "GET", &path, "");
+    assert_eq!(status, 200, "{}", response);
+    let stats = response.get("stats").unwrap();
+    assert_eq!(stats.get("complete"), Some(&Value::Bool(false)));
+    assert!(!response
+        .get("moves")
+        .and_then(|m| m.as_array())
+        .unwrap()
+        .is_empty());
+}